@@ -0,0 +1,115 @@
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use fetch_hotfix::{
+    decode::{Decoded, DecodedValue, Decoder, DecodingResult, WireType},
+    hotfix::Hotfix,
+    resource::UrlPatterns,
+};
+use std::hint::black_box;
+
+/// Builds a gateserver-like message: `urls` resource URLs interleaved with a port and a few flags.
+fn gateserver_fixture(urls: usize) -> Vec<u8> {
+    let kinds = ["asb", "design_data", "lua", "ifix"];
+    let mut fields = Vec::new();
+    for i in 0..urls {
+        let url = format!(
+            "https://autopatch.example.com/{}/V3.0Live/output_{}_0123456789ab/client",
+            kinds[i % kinds.len()],
+            7_000_000 + i
+        );
+        fields.push(string_field(i as u32 + 1, &url));
+        fields.push(varint_field(
+            i as u32 + 1000,
+            if i % 2 == 0 { 1 } else { 23301 },
+        ));
+    }
+
+    DecodingResult {
+        fields,
+        unprocessed: Vec::new(),
+    }
+    .encode()
+}
+
+/// Builds a message nested `depth` levels deep, with a string at the bottom.
+fn nested_fixture(depth: usize) -> Vec<u8> {
+    let mut message = DecodingResult {
+        fields: vec![string_field(1, "leaf")],
+        unprocessed: Vec::new(),
+    };
+    for _ in 0..depth {
+        message = DecodingResult {
+            fields: vec![Decoded {
+                field: 1,
+                wire_type: WireType::Len,
+                is_object: true,
+                value: DecodedValue::Nested(message),
+            }],
+            unprocessed: Vec::new(),
+        };
+    }
+    message.encode()
+}
+
+/// Returns the number of messages nested below the first field of `result`.
+fn nesting_depth(result: &DecodingResult) -> usize {
+    match &result.fields[0].value {
+        DecodedValue::Nested(nested) => 1 + nesting_depth(nested),
+        _ => 0,
+    }
+}
+
+fn string_field(field: u32, value: &str) -> Decoded {
+    Decoded {
+        field,
+        wire_type: WireType::Len,
+        is_object: false,
+        value: DecodedValue::Buffer(value.as_bytes().to_vec()),
+    }
+}
+
+fn varint_field(field: u32, value: u128) -> Decoded {
+    Decoded {
+        field,
+        wire_type: WireType::VarInt,
+        is_object: false,
+        value: DecodedValue::UInt(value),
+    }
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for (name, urls) in [("small", 4), ("medium", 64), ("large", 1024)] {
+        let data = gateserver_fixture(urls);
+        let decoded = Decoder::new(data.clone()).decode().unwrap();
+        assert_eq!(decoded.fields.len(), urls * 2);
+        assert_eq!(decoded.encode(), data);
+        group.bench_with_input(BenchmarkId::from_parameter(name), &data, |b, data| {
+            b.iter(|| Decoder::new(black_box(data.clone())).decode().unwrap())
+        });
+    }
+
+    let data = nested_fixture(64);
+    assert_eq!(
+        nesting_depth(&Decoder::new(data.clone()).decode().unwrap()),
+        64
+    );
+    group.bench_with_input(BenchmarkId::from_parameter("deep"), &data, |b, data| {
+        b.iter(|| Decoder::new(black_box(data.clone())).decode().unwrap())
+    });
+    group.finish();
+}
+
+fn create_from_simple_message(c: &mut Criterion) {
+    let message = Decoder::new(gateserver_fixture(4)).decode().unwrap();
+    let patterns = UrlPatterns::default();
+    let (hotfix, _) = Hotfix::create_from_simple_message(&message, "prod_official", &patterns);
+    assert!(hotfix.missing_urls().is_empty());
+    c.bench_function("create_from_simple_message", |b| {
+        b.iter(|| {
+            Hotfix::create_from_simple_message(black_box(&message), "prod_official", &patterns)
+        })
+    });
+}
+
+criterion_group!(benches, decode, create_from_simple_message);
+criterion_main!(benches);
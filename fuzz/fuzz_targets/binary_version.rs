@@ -0,0 +1,10 @@
+#![no_main]
+
+use fetch_hotfix::binary_version::BinaryVersionData;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(binary_version) = BinaryVersionData::try_from(data.to_vec()) {
+        let _ = binary_version.get_server_pak_type_version();
+    }
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use fetch_hotfix::client_config::ClientStartupConfig;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = ClientStartupConfig::try_from(data.to_vec());
+});
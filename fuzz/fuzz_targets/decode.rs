@@ -0,0 +1,10 @@
+#![no_main]
+
+use fetch_hotfix::decode::Decoder;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(result) = Decoder::new(data.to_vec()).decode() {
+        let _ = result.simplify();
+    }
+});
@@ -0,0 +1,87 @@
+// Every base64 encoding and decoding goes through this module, so the implementation can be swapped in one place.
+
+/// Base64 alphabet of an encoded string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Alphabet {
+    /// The standard alphabet, using `+` and `/`.
+    #[default]
+    Standard,
+    /// The URL and filename safe alphabet, using `-` and `_`. Padding is optional.
+    /// Decoding is lenient and also accepts the `+` and `/` of the standard alphabet.
+    UrlSafe,
+}
+
+/// Error returned for strings that aren't valid base64.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct DecodeError(String);
+
+/// Encodes `bytes` as padded base64 with the standard alphabet.
+pub fn encode(bytes: &[u8]) -> String {
+    rbase64::encode(bytes)
+}
+
+/// Decodes padded base64 written with the standard alphabet.
+pub fn decode(s: &str) -> Result<Vec<u8>, DecodeError> {
+    decode_with(s, Alphabet::Standard)
+}
+
+/// Decodes base64 written with the given alphabet.
+pub fn decode_with(s: &str, alphabet: Alphabet) -> Result<Vec<u8>, DecodeError> {
+    let decoded = match alphabet {
+        Alphabet::Standard => rbase64::decode(s),
+        Alphabet::UrlSafe => {
+            let mut standard = s
+                .chars()
+                .map(|c| match c {
+                    '-' => '+',
+                    '_' => '/',
+                    c => c,
+                })
+                .collect::<String>();
+            while !standard.len().is_multiple_of(4) {
+                standard.push('=');
+            }
+            rbase64::decode(&standard)
+        }
+    };
+
+    decoded.map_err(|e| DecodeError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_both_alphabets() {
+        let bytes = [0xFB, 0xFF, 0xBF];
+
+        assert_eq!(decode_with("+/+/", Alphabet::Standard).unwrap(), bytes);
+        assert_eq!(decode_with("-_-_", Alphabet::UrlSafe).unwrap(), bytes);
+        assert_eq!(decode("+/+/").unwrap(), bytes);
+    }
+
+    #[test]
+    fn url_safe_padding_is_optional() {
+        assert_eq!(decode_with("-_8", Alphabet::UrlSafe).unwrap(), [0xFB, 0xFF]);
+        assert_eq!(
+            decode_with("-_8=", Alphabet::UrlSafe).unwrap(),
+            [0xFB, 0xFF]
+        );
+        assert_eq!(encode(&[0xFB, 0xFF]), "+/8=");
+    }
+
+    #[test]
+    fn standard_decoding_rejects_the_url_safe_alphabet() {
+        assert!(decode_with("-_-_", Alphabet::Standard).is_err());
+    }
+
+    #[test]
+    fn url_safe_decoding_accepts_the_standard_alphabet() {
+        assert_eq!(
+            decode_with("+/+/", Alphabet::UrlSafe).unwrap(),
+            [0xFB, 0xFF, 0xBF]
+        );
+    }
+}
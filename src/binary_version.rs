@@ -9,7 +9,7 @@ pub struct BinaryVersionData {
     pub major_version: u32,
     pub minor_version: u32,
     pub patch_version: u32,
-    _unk: Vec<u8>, // 15 * sizeof(u32)
+    pub unknown: Vec<u8>, // 15 * sizeof(u32)
     // pub unk2: u32,
     // pub unk1: u32,
     // pub unk1: u32,
@@ -37,19 +37,171 @@ pub struct BinaryVersionData {
     pub sdk_ps_client_id: String,
 }
 
+/// Tokens directly preceding the version number in the game version segment, e.g. `OSPRODWin3.0.0`.
+const VERSION_TOKENS: [&str; 4] = ["Win", "Android", "iOS", "PS5"];
+
 impl BinaryVersionData {
+    /// Returns the game version used in dispatch queries.
+    /// If the version string holds several candidates, the one preceded by a known platform token is preferred.
     pub fn get_server_pak_type_version(&self) -> Option<String> {
-        for segment in self.version_string.split('-') {
-            if segment
-                .chars()
-                .all(|c| c.is_ascii_alphanumeric() || c == '.')
-                && segment.contains('.')
-                && segment.chars().filter(|&c| c == '.').count() == 2
-            {
-                return Some(segment.to_string());
-            }
+        let candidates = self.get_all_version_candidates();
+
+        candidates
+            .iter()
+            .find(|candidate| {
+                let prefix = candidate.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+                VERSION_TOKENS.iter().any(|token| prefix.ends_with(token))
+            })
+            .or(candidates.first())
+            .cloned()
+    }
+
+    /// Describes each 4-byte word of `unknown` on its own line, read as a big-endian and a little-endian u32
+    /// and as two u16 of each endianness, to help recognize what they hold.
+    pub fn describe_unknowns(&self) -> String {
+        self.unknown
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(i, word)| {
+                let word: [u8; 4] = word.try_into().unwrap();
+                format!(
+                    "unk{i:02} {:02x?} u32 be={} le={} u16 be={},{} le={},{}\n",
+                    word,
+                    u32::from_be_bytes(word),
+                    u32::from_le_bytes(word),
+                    u16::from_be_bytes([word[0], word[1]]),
+                    u16::from_be_bytes([word[2], word[3]]),
+                    u16::from_le_bytes([word[0], word[1]]),
+                    u16::from_le_bytes([word[2], word[3]]),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the game version as `{major}.{minor}.{patch}`.
+    pub fn game_version(&self) -> String {
+        format!(
+            "{}.{}.{}",
+            self.major_version, self.minor_version, self.patch_version
+        )
+    }
+
+    /// Orders two builds by their major, minor and patch version, then by revision.
+    pub fn cmp_version(&self, other: &Self) -> std::cmp::Ordering {
+        (
+            self.major_version,
+            self.minor_version,
+            self.patch_version,
+            self.revision,
+        )
+            .cmp(&(
+                other.major_version,
+                other.minor_version,
+                other.patch_version,
+                other.revision,
+            ))
+    }
+
+    /// Returns every `-` separated segment of the version string that looks like an `x.y.z` version.
+    pub fn get_all_version_candidates(&self) -> Vec<String> {
+        self.version_string
+            .split('-')
+            .filter(|segment| {
+                segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '.')
+                    && segment.chars().filter(|&c| c == '.').count() == 2
+            })
+            .map(String::from)
+            .collect()
+    }
+}
+
+/// Parses the fields following the branch name for one BinaryVersion layout.
+pub type LayoutParser =
+    fn(branch: String, reader: &mut Cursor<Vec<u8>>) -> Result<BinaryVersionData, std::io::Error>;
+
+/// A BinaryVersion field layout, used for the builds whose branch name it matches.
+#[derive(Clone, Copy)]
+pub struct Layout {
+    /// Human readable name of the layout.
+    pub name: &'static str,
+    /// Returns whether the layout applies to the given branch.
+    pub matches: fn(branch: &str) -> bool,
+    /// Parses the remaining fields.
+    pub parse: LayoutParser,
+}
+
+/// The layout used by current builds.
+pub const CURRENT_LAYOUT: Layout = Layout {
+    name: "current",
+    matches: |_| true,
+    parse: parse_current_layout,
+};
+
+fn parse_current_layout(
+    branch: String,
+    reader: &mut Cursor<Vec<u8>>,
+) -> Result<BinaryVersionData, std::io::Error> {
+    Ok(BinaryVersionData {
+        branch,
+        revision: reader.read_u32_be()?,
+        major_version: reader.read_u32_be()?,
+        minor_version: reader.read_u32_be()?,
+        patch_version: reader.read_u32_be()?,
+        unknown: reader.read_bytes(4 * 15)?,
+        time: reader.read_string()?,
+        pak_type: reader.read_string()?,
+        pak_type_detail: reader.read_string()?,
+        start_asset: reader.read_string()?,
+        start_design_data: reader.read_string()?,
+        dispatch_seed: reader.read_string()?,
+        version_string: reader.read_string()?,
+        version_hash: reader.read_string()?,
+        game_core_version: reader.read_u32_be()?,
+        is_enable_exclude_asset: reader.read_bool()?,
+        sdk_ps_client_id: reader.read_string()?,
+    })
+}
+
+/// The set of known BinaryVersion layouts.
+/// The branch name is read first and the first layout matching it parses the rest of the file.
+pub struct LayoutRegistry {
+    layouts: Vec<Layout>,
+}
+
+impl Default for LayoutRegistry {
+    fn default() -> Self {
+        Self {
+            layouts: vec![CURRENT_LAYOUT],
         }
-        None
+    }
+}
+
+impl LayoutRegistry {
+    /// Registers an alternate layout. It takes precedence over every previously registered layout.
+    pub fn register(&mut self, layout: Layout) {
+        self.layouts.insert(0, layout);
+    }
+
+    /// Returns the layout used for the given branch.
+    pub fn select(&self, branch: &str) -> Option<&Layout> {
+        self.layouts.iter().find(|layout| (layout.matches)(branch))
+    }
+
+    /// Parses BinaryVersion data using the layout selected by its branch name.
+    pub fn parse(&self, value: Vec<u8>) -> Result<BinaryVersionData, std::io::Error> {
+        let mut reader = Cursor::new(value);
+        let branch = reader.read_string()?;
+
+        let layout = self.select(&branch).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("no BinaryVersion layout matches branch {branch}"),
+            )
+        })?;
+
+        (layout.parse)(branch, &mut reader)
     }
 }
 
@@ -57,26 +209,162 @@ impl TryFrom<Vec<u8>> for BinaryVersionData {
     type Error = std::io::Error;
 
     fn try_from(value: Vec<u8>) -> Result<Self, std::io::Error> {
-        let mut reader = Cursor::new(value);
+        LayoutRegistry::default().parse(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Vec<u8> {
+        let mut buf = vec![1, s.len() as u8];
+        buf.extend_from_slice(s.as_bytes());
+        buf
+    }
+
+    /// Builds a BinaryVersion file in the current layout.
+    fn binary_version(branch: &str, version_string: &str) -> Vec<u8> {
+        let mut buf = string(branch);
+        for value in [7563532_u32, 2, 2, 0] {
+            buf.extend_from_slice(&value.to_be_bytes());
+        }
+        buf.extend_from_slice(&[0; 4 * 15]);
+        for s in [
+            "2024-04-01",
+            "PAK",
+            "",
+            "",
+            "",
+            "seed",
+            version_string,
+            "hash",
+        ] {
+            buf.extend(string(s));
+        }
+        buf.extend_from_slice(&1_u32.to_be_bytes());
+        buf.push(0);
+        buf.extend(string(""));
+        buf
+    }
+
+    fn parse_old_layout(
+        branch: String,
+        reader: &mut Cursor<Vec<u8>>,
+    ) -> Result<BinaryVersionData, std::io::Error> {
+        let mut data = parse_current_layout(branch, reader)?;
+        data.pak_type = String::from("old");
+        Ok(data)
+    }
+
+    const OLD_LAYOUT: Layout = Layout {
+        name: "old",
+        matches: |branch| branch.starts_with("OLD"),
+        parse: parse_old_layout,
+    };
+
+    #[test]
+    fn selects_the_layout_by_branch() {
+        let mut registry = LayoutRegistry::default();
+        registry.register(OLD_LAYOUT);
+
+        assert_eq!(registry.select("OLDPRODWin1.0.0").unwrap().name, "old");
+        assert_eq!(registry.select("CNPRODWin2.2.0").unwrap().name, "current");
+
+        let old = registry
+            .parse(binary_version("OLDPRODWin1.0.0", "1-OLDPRODWin1.0.0"))
+            .unwrap();
+        assert_eq!(old.pak_type, "old");
+        let current = registry
+            .parse(binary_version("CNPRODWin2.2.0", "1-CNPRODWin2.2.0"))
+            .unwrap();
+        assert_eq!(current.pak_type, "PAK");
+        assert_eq!(current.dispatch_seed, "seed");
+    }
+
+    fn with_version_string(version_string: &str) -> BinaryVersionData {
+        BinaryVersionData::try_from(binary_version("CNPRODWin2.2.0", version_string)).unwrap()
+    }
+
+    #[test]
+    fn returns_every_version_candidate() {
+        let data = with_version_string("7563532-BetaLive1.0.5-CNPRODWin2.2.0-x.y");
+
+        assert_eq!(
+            data.get_all_version_candidates(),
+            ["BetaLive1.0.5", "CNPRODWin2.2.0"]
+        );
+    }
+
+    #[test]
+    fn prefers_the_candidate_after_a_platform_token() {
+        let data = with_version_string("7563532-BetaLive1.0.5-CNPRODWin2.2.0");
+        assert_eq!(
+            data.get_server_pak_type_version().as_deref(),
+            Some("CNPRODWin2.2.0")
+        );
+
+        let data = with_version_string("7563532-BetaLive1.0.5-Custom2.2.0");
+        assert_eq!(
+            data.get_server_pak_type_version().as_deref(),
+            Some("BetaLive1.0.5")
+        );
+
+        let data = with_version_string("7563532");
+        assert_eq!(data.get_server_pak_type_version(), None);
+    }
+
+    fn with_version(major: u32, minor: u32, patch: u32, revision: u32) -> BinaryVersionData {
+        BinaryVersionData {
+            major_version: major,
+            minor_version: minor,
+            patch_version: patch,
+            revision,
+            ..with_version_string("")
+        }
+    }
+
+    #[test]
+    fn formats_the_game_version() {
+        assert_eq!(with_version(2, 2, 0, 1).game_version(), "2.2.0");
+        assert_eq!(with_version(3, 10, 51, 1).game_version(), "3.10.51");
+    }
+
+    #[test]
+    fn orders_by_version_then_revision() {
+        use std::cmp::Ordering;
+
+        let base = with_version(2, 2, 0, 100);
+        assert_eq!(
+            base.cmp_version(&with_version(2, 2, 0, 100)),
+            Ordering::Equal
+        );
+        assert_eq!(
+            base.cmp_version(&with_version(2, 2, 0, 101)),
+            Ordering::Less
+        );
+        assert_eq!(base.cmp_version(&with_version(2, 10, 0, 1)), Ordering::Less);
+        assert_eq!(
+            base.cmp_version(&with_version(1, 9, 9, 999)),
+            Ordering::Greater
+        );
+        assert_eq!(
+            base.cmp_version(&with_version(2, 1, 5, 100)),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn describes_unknowns_in_both_endians() {
+        let data = BinaryVersionData {
+            unknown: vec![0x00, 0x00, 0x01, 0x02, 0xFF, 0xFF, 0xFF, 0xFF],
+            ..with_version_string("")
+        };
 
-        Ok(Self {
-            branch: reader.read_string()?,
-            revision: reader.read_u32_be()?,
-            major_version: reader.read_u32_be()?,
-            minor_version: reader.read_u32_be()?,
-            patch_version: reader.read_u32_be()?,
-            _unk: reader.read_bytes(4 * 15)?,
-            time: reader.read_string()?,
-            pak_type: reader.read_string()?,
-            pak_type_detail: reader.read_string()?,
-            start_asset: reader.read_string()?,
-            start_design_data: reader.read_string()?,
-            dispatch_seed: reader.read_string()?,
-            version_string: reader.read_string()?,
-            version_hash: reader.read_string()?,
-            game_core_version: reader.read_u32_be()?,
-            is_enable_exclude_asset: reader.read_bool()?,
-            sdk_ps_client_id: reader.read_string()?,
-        })
+        assert_eq!(
+            data.describe_unknowns(),
+            "unk00 [00, 00, 01, 02] u32 be=258 le=33619968 u16 be=0,258 le=0,513\n\
+             unk01 [ff, ff, ff, ff] u32 be=4294967295 le=4294967295 u16 be=65535,65535 le=65535,65535\n"
+        );
     }
 }
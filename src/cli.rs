@@ -0,0 +1,734 @@
+use crate::config::Config;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, parser::ValueSource};
+use fetch_hotfix::{
+    error::AppError,
+    fetch::{ClientOptions, DEFAULT_VERIFY_CONCURRENCY, RetryPolicy},
+    hotfix::{DEFAULT_MESSAGE_NAME, ProtoOptions, is_valid_proto_identifier},
+    language::Language,
+    output::{EmulatorConfig, Json, OutputFormat},
+    params::{ChannelOverride, DEFAULT_ENVELOPE_FIELD, DispatchParams},
+    platform::Platform,
+    resource::ResourceKind,
+    util::{DEFAULT_MAX_GAME_FILE_SIZE, decode_hex, read_input},
+};
+use reqwest::header::{HeaderName, HeaderValue};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+/// Fetches the hotfix URLs of an installed HSR client.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Args {
+    /// Read default option values from this TOML file instead of `fetch-hotfix.toml`.
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Language of the client: chs, cht, en, kr, jp, fr, de, es, pt, ru, th, vi or id.
+    #[arg(long, default_value_t = Language::En)]
+    pub language: Language,
+    /// Send this raw `language_type` code instead of the one of `--language`.
+    #[arg(long, conflicts_with = "language")]
+    pub language_type: Option<u32>,
+    /// Platform of the selected client: pc, android, ios or ps.
+    #[arg(long, default_value_t = Platform::Pc)]
+    pub platform: Platform,
+    /// Open the folder picker in this directory, e.g. a games library, instead of the last selected folder.
+    #[arg(long, value_name = "DIR")]
+    pub start_dir: Option<PathBuf>,
+    /// Look for BinaryVersion.bytes and ClientConfig.bytes under this path, relative to the selected folder,
+    /// instead of the streaming assets folder of `--platform`.
+    #[arg(long, value_name = "REL")]
+    pub assets_subpath: Option<PathBuf>,
+    /// Refuse to read BinaryVersion.bytes or ClientConfig.bytes if they are larger than this many bytes.
+    #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_MAX_GAME_FILE_SIZE)]
+    pub max_file_size: u64,
+    /// Override the `platform_type` query parameter of the gateway request, which is otherwise derived from `--platform`.
+    #[arg(long)]
+    pub gateway_platform_type: Option<u32>,
+    /// Override the `channel_id` query parameter.
+    #[arg(long)]
+    pub channel_id: Option<u32>,
+    /// Override the `sub_channel_id` query parameter.
+    #[arg(long)]
+    pub sub_channel_id: Option<u32>,
+    /// Use other channel identifiers for the gateway of one region. Can be repeated.
+    #[arg(long = "region-channel", value_name = "REGION=CHANNEL[:SUB_CHANNEL]", value_parser = parse_region_channel)]
+    pub region_channels: Vec<(String, ChannelOverride)>,
+    /// Query this dispatch URL instead of the first one listed in ClientConfig.
+    /// A `file://` URL is read from disk as the base64 dispatch response.
+    #[arg(long, value_name = "URL")]
+    pub dispatch_url: Option<String>,
+    /// Read the base64 gateserver response from this file ("-" for stdin) instead of querying the servers.
+    #[arg(long)]
+    pub gateway_file: Option<PathBuf>,
+    /// Read the base64 gateserver response from the clipboard instead of querying the servers.
+    #[cfg(feature = "clipboard")]
+    #[arg(long, conflicts_with_all = ["gateway_file", "hex_input", "watch"])]
+    pub from_clipboard: bool,
+    /// Decode this hex encoded gateserver message instead of querying the servers.
+    /// Takes the hex itself, or a file holding it ("-" for stdin).
+    #[arg(long, value_name = "HEX|FILE", conflicts_with = "gateway_file")]
+    pub hex_input: Option<String>,
+    /// Game version used to name the output files of `--gateway-file`, `--from-clipboard` and `--hex-input`,
+    /// which decode the response without reading the game files.
+    #[arg(long, value_name = "VERSION")]
+    pub game_version: Option<String>,
+    /// Field holding the base64 payload of responses wrapped in a JSON object.
+    #[arg(long, value_name = "FIELD", default_value = DEFAULT_ENVELOPE_FIELD)]
+    pub envelope_field: String,
+    /// Query the gateway with `is_need_url=0`, which omits most resource URLs from the response.
+    /// Implies `--keep-going`, as the missing URLs are expected.
+    #[arg(long)]
+    pub no_need_url: bool,
+    /// Trust this PEM encoded root certificate in addition to the system ones.
+    #[arg(long, value_name = "PEM")]
+    pub ca_cert: Option<PathBuf>,
+    /// Disable TLS certificate verification. Only use this against servers you trust.
+    #[arg(long)]
+    pub insecure: bool,
+    /// Send this `Key: Value` header with the dispatch and gateway requests. Can be repeated.
+    #[arg(long = "header", value_name = "KEY: VALUE", value_parser = parse_header)]
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    /// Fetch the hotfix again every this many seconds until Ctrl-C is pressed,
+    /// writing the output files only when the gateserver message changed.
+    #[arg(
+        long,
+        value_name = "SECS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = ["gateway_file", "hex_input"]
+    )]
+    pub watch: Option<u64>,
+    /// Fetch the hotfix of each of these game folders in turn instead of picking one in a dialog. Can be repeated.
+    /// Versions completed by an earlier run, as recorded in `--batch-state`, are skipped.
+    #[arg(
+        long = "batch",
+        value_name = "DIR",
+        conflicts_with_all = ["gateway_file", "hex_input", "watch"]
+    )]
+    pub batch: Vec<PathBuf>,
+    /// File recording the versions completed by `--batch`.
+    #[arg(long, value_name = "PATH", default_value = "fetch-hotfix-batch.json")]
+    pub batch_state: PathBuf,
+    /// Fetch the `--batch` versions again even if an earlier run completed them.
+    #[arg(long)]
+    pub force: bool,
+    /// Abort the dispatch and gateway queries if they take longer than this many seconds in total.
+    #[arg(long, value_name = "SECS")]
+    pub deadline: Option<u64>,
+    /// Abort the dispatch request if it takes longer than this many seconds.
+    #[arg(long, value_name = "SECS")]
+    pub dispatch_timeout: Option<u64>,
+    /// Abort the gateway request if it takes longer than this many seconds.
+    #[arg(long, value_name = "SECS")]
+    pub gateway_timeout: Option<u64>,
+    /// Retry the queries of each dispatch URL up to this many times when they fail with a timeout, a connection error or a 5xx response.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+    /// Query the servers at most this many times in total, across all dispatch URLs and retries.
+    #[arg(long, value_name = "N")]
+    pub max_attempts: Option<u32>,
+    /// Check that every resource URL is reachable with a HEAD request before writing the output.
+    #[arg(long)]
+    pub verify: bool,
+    /// Send at most this many `--verify` requests at once.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_VERIFY_CONCURRENCY)]
+    pub verify_concurrency: usize,
+    /// Fail unless the gateserver message has all of these top-level field numbers, e.g. `5,7,9`.
+    #[arg(long, value_name = "NUMBERS", value_delimiter = ',')]
+    pub require_fields: Vec<u32>,
+    /// Write the output even if some resource URLs are missing, instead of failing.
+    #[arg(long)]
+    pub keep_going: bool,
+    /// Write the hotfix, metadata and proto files into this directory, creating it if needed.
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    pub out_dir: PathBuf,
+    /// Only print the URL of this resource to stdout (asset_bundle, ex_resource, lua or ifix),
+    /// failing if it wasn't found. No files are written.
+    #[arg(long, value_name = "RESOURCE")]
+    pub only: Option<ResourceKind>,
+    /// Format of the hotfix file: json, or emulator for `key=value` lines.
+    #[arg(long, default_value_t = HotfixFormat::Json)]
+    pub format: HotfixFormat,
+    /// Write minified JSON instead of pretty-printing it.
+    #[arg(long)]
+    pub compact: bool,
+    /// Also write the decoded dispatch response, with every region, to `hotfix-<version>.dispatch.json`.
+    #[arg(long)]
+    pub save_dispatch: bool,
+    /// Also keep every distinct inferred proto under `<DIR>/<version>/`.
+    #[arg(long, value_name = "DIR")]
+    pub save_proto_dir: Option<PathBuf>,
+    /// Also write the top-level gateserver fields to this CSV file.
+    #[arg(long, value_name = "PATH")]
+    pub csv: Option<PathBuf>,
+    /// Also write the field tree of the gateserver message to this Graphviz DOT file.
+    #[arg(long, value_name = "PATH")]
+    pub dot: Option<PathBuf>,
+    /// Also write the inferred proto and the raw gateserver message into this directory, for use with `protoc --decode`.
+    #[arg(long, value_name = "DIR")]
+    pub protoc_bundle: Option<PathBuf>,
+    /// Also write the inferred fields as a Rust struct to this file, with the prost attributes as comments.
+    #[arg(long, value_name = "PATH")]
+    pub rust_module: Option<PathBuf>,
+    /// Name of the message in the inferred proto.
+    #[arg(long, default_value = DEFAULT_MESSAGE_NAME, value_parser = parse_message_name)]
+    pub proto_message_name: String,
+    /// Align the types, names and numbers of the inferred proto fields in columns.
+    #[arg(long)]
+    pub pretty_proto: bool,
+    /// Annotate each field of the inferred proto with a sample of its value.
+    #[arg(long)]
+    pub proto_comments: bool,
+    /// Warn when the wire types of the gateserver fields are distributed differently than in the baseline
+    /// stored for this version in this JSON file. The first run for a version records its baseline.
+    #[arg(long, value_name = "PATH")]
+    pub wire_baseline: Option<PathBuf>,
+    /// Largest change of the share of a wire type, from 0 to 1, tolerated by `--wire-baseline`.
+    #[arg(long, value_name = "RATIO", default_value_t = 0.1)]
+    pub wire_baseline_threshold: f64,
+    /// Append the version, resource URLs and a hash of the gateserver message of this run to this JSON Lines file.
+    #[arg(long, value_name = "PATH")]
+    pub history: Option<PathBuf>,
+    /// Compare the result against a previously saved hotfix JSON and exit with code 3 if anything changed.
+    #[arg(long, value_name = "PREVIOUS_JSON")]
+    pub diff: Option<PathBuf>,
+    /// Print the unknown words of BinaryVersion as big and little endian integers.
+    #[arg(long)]
+    pub unknowns_both_endian: bool,
+    /// Print the JSON Schema of the hotfix output and exit.
+    #[arg(long)]
+    pub emit_schema: bool,
+    /// Print every top-level gateserver field as a JSON line to stdout instead of writing the output files.
+    #[arg(long)]
+    pub raw_output: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Commands run instead of fetching the hotfix.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Print the game version and branch of an installed client, without querying any server.
+    Version {
+        /// Game folder to read, instead of picking one in a dialog.
+        #[arg(long, value_name = "DIR")]
+        path: PathBuf,
+    },
+}
+
+impl Args {
+    /// Parses the command line, then fills in every option it doesn't set from the config file.
+    pub fn load() -> std::io::Result<Self> {
+        let matches = Self::command().get_matches();
+        let mut args = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+        let config = match &args.config {
+            Some(path) => Config::read(path)?,
+            None => Config::read_default()?,
+        };
+        args.apply_config(config, &matches);
+
+        Ok(args)
+    }
+
+    /// Replaces the values of the options that were not passed on the command line by those of `config`.
+    fn apply_config(&mut self, config: Config, matches: &clap::ArgMatches) {
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        if let Some(platform) = config.platform.filter(|_| !from_cli("platform")) {
+            self.platform = platform;
+        }
+        if let Some(language) = config.language.filter(|_| !from_cli("language")) {
+            self.language = language;
+        }
+        // A language picked on the command line wins over a raw code from the config file
+        if !from_cli("language") && !from_cli("language_type") {
+            self.language_type = config.language_type;
+        }
+        if !from_cli("gateway_platform_type") {
+            self.gateway_platform_type = config.gateway_platform_type;
+        }
+        if !from_cli("channel_id") {
+            self.channel_id = config.channel_id;
+        }
+        if !from_cli("sub_channel_id") {
+            self.sub_channel_id = config.sub_channel_id;
+        }
+        if !from_cli("deadline") {
+            self.deadline = config.deadline;
+        }
+        if !from_cli("dispatch_timeout") {
+            self.dispatch_timeout = config.dispatch_timeout;
+        }
+        if !from_cli("gateway_timeout") {
+            self.gateway_timeout = config.gateway_timeout;
+        }
+        if let Some(out_dir) = config.out_dir.filter(|_| !from_cli("out_dir")) {
+            self.out_dir = out_dir;
+        }
+    }
+
+    /// Builds the dispatch parameters for `version`, applying any overrides passed on the command line.
+    pub fn dispatch_params(&self, version: &str) -> DispatchParams {
+        let mut params = DispatchParams::new(version)
+            .with_platform(self.platform)
+            .with_language(self.language)
+            .with_envelope_field(&self.envelope_field)
+            .with_is_need_url(!self.no_need_url);
+        if let Some(language_type) = self.language_type {
+            params = params.with_language_type(language_type);
+        }
+        if let Some(platform_type) = self.gateway_platform_type {
+            params = params.with_gateway_platform_type(platform_type);
+        }
+        if let Some(channel_id) = self.channel_id {
+            params = params.with_channel_id(channel_id);
+        }
+        if let Some(sub_channel_id) = self.sub_channel_id {
+            params = params.with_sub_channel_id(sub_channel_id);
+        }
+        if let Some(secs) = self.dispatch_timeout {
+            params = params.with_dispatch_timeout(Duration::from_secs(secs));
+        }
+        if let Some(secs) = self.gateway_timeout {
+            params = params.with_gateway_timeout(Duration::from_secs(secs));
+        }
+        for (region_name, channels) in &self.region_channels {
+            params = params.with_region_channel(region_name, *channels);
+        }
+        params
+    }
+
+    /// Path of the folder holding the game files, relative to the selected folder.
+    pub fn assets_subpath(&self) -> &Path {
+        self.assets_subpath
+            .as_deref()
+            .unwrap_or(self.platform.streaming_assets_path())
+    }
+
+    /// Returns the gateserver response passed with `--gateway-file` or `--from-clipboard`, if any.
+    pub fn gateway_input(&self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if let Some(gateway_file) = &self.gateway_file {
+            return Ok(Some(read_input(gateway_file)?));
+        }
+
+        #[cfg(feature = "clipboard")]
+        if self.from_clipboard {
+            return Ok(Some(fetch_hotfix::util::read_clipboard()?));
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the gateserver message passed with `--hex-input`, if any.
+    pub fn hex_input(&self) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        let Some(hex_input) = &self.hex_input else {
+            return Ok(None);
+        };
+
+        let path = Path::new(hex_input);
+        let hex = if hex_input == "-" || path.is_file() {
+            read_input(path)?
+        } else {
+            hex_input.clone()
+        };
+
+        Ok(Some(decode_hex(&hex).map_err(AppError::Hex)?))
+    }
+
+    /// Builds the retry policy of the dispatch URL fallback.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            retries_per_url: self.retries,
+            max_attempts: self.max_attempts.unwrap_or(u32::MAX),
+        }
+    }
+
+    /// Builds the HTTP client options, reading the CA certificate if one was passed.
+    pub fn client_options(&self) -> std::io::Result<ClientOptions> {
+        Ok(ClientOptions {
+            ca_cert: self.ca_cert.as_deref().map(std::fs::read).transpose()?,
+            insecure: self.insecure,
+            headers: self.headers.clone(),
+        })
+    }
+
+    /// Builds the options used to render the inferred proto.
+    pub fn proto_options(&self) -> ProtoOptions {
+        ProtoOptions {
+            message_name: self.proto_message_name.clone(),
+            aligned: self.pretty_proto,
+            comments: self.proto_comments,
+        }
+    }
+
+    /// Returns the format the hotfix file is written in.
+    pub fn output_format(&self) -> Box<dyn OutputFormat> {
+        match self.format {
+            HotfixFormat::Json => Box::new(Json {
+                compact: self.compact,
+            }),
+            HotfixFormat::Emulator => Box::new(EmulatorConfig),
+        }
+    }
+
+    /// Serializes `value` as JSON, minified if `--compact` was passed and pretty-printed otherwise.
+    pub fn to_json<T: serde::Serialize>(&self, value: &T) -> serde_json::Result<String> {
+        if self.compact {
+            serde_json::to_string(value)
+        } else {
+            serde_json::to_string_pretty(value)
+        }
+    }
+}
+
+/// Formats the hotfix file can be written in, see `OutputFormat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotfixFormat {
+    Json,
+    Emulator,
+}
+
+impl FromStr for HotfixFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(HotfixFormat::Json),
+            "emulator" => Ok(HotfixFormat::Emulator),
+            _ => Err(format!("unknown format: {s}")),
+        }
+    }
+}
+
+impl fmt::Display for HotfixFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HotfixFormat::Json => "json",
+            HotfixFormat::Emulator => "emulator",
+        })
+    }
+}
+
+/// Parses a `REGION=CHANNEL[:SUB_CHANNEL]` region channel override, where either id may be left empty.
+fn parse_region_channel(s: &str) -> Result<(String, ChannelOverride), String> {
+    let (region_name, ids) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected REGION=CHANNEL[:SUB_CHANNEL], got {s}"))?;
+    let (channel_id, sub_channel_id) = ids.split_once(':').unwrap_or((ids, ""));
+
+    let parse_id = |id: &str| {
+        (!id.is_empty())
+            .then(|| {
+                id.parse::<u32>()
+                    .map_err(|e| format!("invalid channel id {id}: {e}"))
+            })
+            .transpose()
+    };
+
+    Ok((
+        region_name.to_string(),
+        ChannelOverride {
+            channel_id: parse_id(channel_id)?,
+            sub_channel_id: parse_id(sub_channel_id)?,
+        },
+    ))
+}
+
+/// Parses a `Key: Value` request header, rejecting names and values that can't be sent over HTTP.
+fn parse_header(s: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected KEY: VALUE, got {s}"))?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|e| format!("invalid header name {name:?}: {e}"))?;
+    let value = HeaderValue::from_str(value.trim())
+        .map_err(|e| format!("invalid value for header {name}: {e}"))?;
+
+    Ok((name, value))
+}
+
+/// Parses a proto message name, rejecting anything that is not a valid proto identifier.
+fn parse_message_name(s: &str) -> Result<String, String> {
+    if is_valid_proto_identifier(s) {
+        Ok(s.to_string())
+    } else {
+        Err(format!("{s} is not a valid proto identifier"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("fetch-hotfix").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn platform_sets_both_platform_types() {
+        let params = parse(&["--platform", "android"]).dispatch_params("3.0.0");
+
+        assert_eq!(params.platform_type, 2);
+        assert_eq!(params.gateway_platform_type, 2);
+    }
+
+    #[test]
+    fn gateway_platform_type_overrides_platform() {
+        let params = parse(&["--platform", "android", "--gateway-platform-type", "7"])
+            .dispatch_params("3.0.0");
+
+        assert_eq!(params.platform_type, 2);
+        assert_eq!(params.gateway_platform_type, 7);
+    }
+
+    #[test]
+    fn compact_json_has_no_newlines() {
+        let hotfix = fetch_hotfix::hotfix::Hotfix {
+            asset_bundle_url: String::from("https://example.com/asb/output_1_a/client"),
+            ports: vec![23301, 23302],
+            ..Default::default()
+        };
+
+        let compact = parse(&["--compact"]).to_json(&hotfix).unwrap();
+        assert!(!compact.contains('\n'));
+
+        let pretty = parse(&[]).to_json(&hotfix).unwrap();
+        assert!(pretty.contains('\n'));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&compact).unwrap(),
+            serde_json::from_str::<serde_json::Value>(&pretty).unwrap()
+        );
+    }
+
+    #[test]
+    fn client_options_read_the_ca_cert() {
+        let dir = tempfile::tempdir().unwrap();
+        let pem_path = dir.path().join("ca.pem");
+        std::fs::write(&pem_path, "-----BEGIN CERTIFICATE-----").unwrap();
+
+        let options = parse(&["--ca-cert", pem_path.to_str().unwrap(), "--insecure"])
+            .client_options()
+            .unwrap();
+
+        assert_eq!(
+            options.ca_cert.as_deref(),
+            Some(&b"-----BEGIN CERTIFICATE-----"[..])
+        );
+        assert!(options.insecure);
+    }
+
+    #[test]
+    fn rejects_invalid_message_names() {
+        assert_eq!(
+            parse(&["--proto-message-name", "RegionGateserver"]).proto_message_name,
+            "RegionGateserver"
+        );
+        assert!(Args::try_parse_from(["fetch-hotfix", "--proto-message-name", "2Gate"]).is_err());
+    }
+
+    #[test]
+    fn language_sets_the_language_type() {
+        assert_eq!(parse(&[]).dispatch_params("3.0.0").language_type, 3);
+        assert_eq!(
+            parse(&["--language", "jp"])
+                .dispatch_params("3.0.0")
+                .language_type,
+            5
+        );
+        assert!(Args::try_parse_from(["fetch-hotfix", "--language", "xx"]).is_err());
+    }
+
+    #[test]
+    fn assets_subpath_overrides_the_platform_path() {
+        assert_eq!(
+            parse(&["--platform", "android"]).assets_subpath(),
+            Path::new("assets")
+        );
+        assert_eq!(
+            parse(&["--platform", "android", "--assets-subpath", "Repack/Data"]).assets_subpath(),
+            Path::new("Repack/Data")
+        );
+    }
+
+    /// Parses `args`, then applies the TOML config `config` like `Args::load`.
+    fn parse_with_config(args: &[&str], config: &str) -> Args {
+        let matches = Args::command()
+            .try_get_matches_from(std::iter::once("fetch-hotfix").chain(args.iter().copied()))
+            .unwrap();
+        let mut parsed = Args::from_arg_matches(&matches).unwrap();
+        parsed.apply_config(toml::from_str(config).unwrap(), &matches);
+        parsed
+    }
+
+    #[test]
+    fn command_line_options_override_the_config_file() {
+        let config = r#"
+            platform = "android"
+            language = "jp"
+            channel_id = 5
+            deadline = 30
+            out_dir = "from-config"
+        "#;
+
+        let args = parse_with_config(&["--channel-id", "9", "--out-dir", "from-cli"], config);
+
+        assert_eq!(args.platform, Platform::Android);
+        assert_eq!(args.language, Language::Jp);
+        assert_eq!(args.channel_id, Some(9));
+        assert_eq!(args.deadline, Some(30));
+        assert_eq!(args.out_dir, Path::new("from-cli"));
+
+        let params = args.dispatch_params("2.2.0");
+        assert_eq!(params.platform_type, 2);
+        assert_eq!(params.language_type, 5);
+        assert_eq!(params.channel_id, 9);
+    }
+
+    #[test]
+    fn timeouts_are_read_from_the_config_file() {
+        let config = "dispatch_timeout = 5\ngateway_timeout = 20\n";
+
+        let params = parse_with_config(&[], config).dispatch_params("2.2.0");
+        assert_eq!(params.dispatch_timeout, Some(Duration::from_secs(5)));
+        assert_eq!(params.gateway_timeout, Some(Duration::from_secs(20)));
+
+        let params =
+            parse_with_config(&["--gateway-timeout", "40"], config).dispatch_params("2.2.0");
+        assert_eq!(params.dispatch_timeout, Some(Duration::from_secs(5)));
+        assert_eq!(params.gateway_timeout, Some(Duration::from_secs(40)));
+    }
+
+    #[test]
+    fn language_on_the_command_line_beats_a_language_type_in_the_config_file() {
+        let args = parse_with_config(&["--language", "kr"], "language_type = 12");
+
+        assert_eq!(args.language_type, None);
+        assert_eq!(args.dispatch_params("2.2.0").language_type, 4);
+    }
+
+    #[test]
+    fn format_selects_the_output_format() {
+        assert_eq!(parse(&[]).output_format().extension(), "json");
+        assert_eq!(
+            parse(&["--format", "emulator"]).output_format().extension(),
+            "cfg"
+        );
+        assert!(Args::try_parse_from(["fetch-hotfix", "--format", "yaml"]).is_err());
+    }
+
+    #[test]
+    fn max_attempts_bounds_the_retry_policy() {
+        assert_eq!(parse(&[]).retry_policy(), RetryPolicy::default());
+        assert_eq!(
+            parse(&["--retries", "2", "--max-attempts", "5"]).retry_policy(),
+            RetryPolicy {
+                retries_per_url: 2,
+                max_attempts: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn start_dir_is_passed_to_the_dialog() {
+        assert_eq!(parse(&[]).start_dir, None);
+        assert_eq!(
+            parse(&["--start-dir", "/games/SteamLibrary"])
+                .start_dir
+                .as_deref(),
+            Some(Path::new("/games/SteamLibrary"))
+        );
+    }
+
+    #[test]
+    fn proto_flags_set_the_proto_options() {
+        assert_eq!(parse(&[]).proto_options(), ProtoOptions::default());
+        assert_eq!(
+            parse(&["--pretty-proto", "--proto-comments"]).proto_options(),
+            ProtoOptions {
+                aligned: true,
+                comments: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn stage_timeouts_are_set_separately() {
+        let params =
+            parse(&["--dispatch-timeout", "5", "--gateway-timeout", "20"]).dispatch_params("2.2.0");
+        assert_eq!(params.dispatch_timeout, Some(Duration::from_secs(5)));
+        assert_eq!(params.gateway_timeout, Some(Duration::from_secs(20)));
+
+        let params = parse(&["--gateway-timeout", "20"]).dispatch_params("2.2.0");
+        assert_eq!(params.dispatch_timeout, None);
+        assert_eq!(params.gateway_timeout, Some(Duration::from_secs(20)));
+    }
+
+    #[test]
+    fn verify_concurrency_defaults_to_four() {
+        assert_eq!(parse(&[]).verify_concurrency, 4);
+        assert_eq!(
+            parse(&["--verify", "--verify-concurrency", "8"]).verify_concurrency,
+            8
+        );
+    }
+
+    #[test]
+    fn parses_custom_headers() {
+        let options = parse(&[
+            "--header",
+            "X-Auth-Token: secret",
+            "--header",
+            "X-Region:cn",
+        ])
+        .client_options()
+        .unwrap();
+
+        assert_eq!(
+            options
+                .headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.to_str().unwrap()))
+                .collect::<Vec<_>>(),
+            [("x-auth-token", "secret"), ("x-region", "cn")]
+        );
+
+        for header in ["X-Auth-Token", "Bad Name: value", "X-Auth: line\nbreak"] {
+            assert!(
+                Args::try_parse_from(["fetch-hotfix", "--header", header]).is_err(),
+                "{header:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn hex_input_is_read_inline_or_from_a_file() {
+        assert_eq!(
+            parse(&["--hex-input", "0x08 0x85 0xb6 0x01"])
+                .hex_input()
+                .unwrap(),
+            Some(vec![0x08, 0x85, 0xB6, 0x01])
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture.hex");
+        std::fs::write(&path, "0885\nb601\n").unwrap();
+        assert_eq!(
+            parse(&["--hex-input", path.to_str().unwrap()])
+                .hex_input()
+                .unwrap(),
+            Some(vec![0x08, 0x85, 0xB6, 0x01])
+        );
+
+        assert!(parse(&[]).hex_input().unwrap().is_none());
+        let error = parse(&["--hex-input", "0x123"]).hex_input().unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<AppError>(),
+            Some(AppError::Hex(_))
+        ));
+    }
+}
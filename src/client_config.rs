@@ -1,6 +1,5 @@
-use varint_rs::VarintReader;
-
 use crate::util::CursorExt as _;
+use std::collections::HashSet;
 use std::io::{Cursor, Read};
 
 #[derive(Debug)]
@@ -25,12 +24,197 @@ impl TryFrom<Vec<u8>> for ClientStartupConfig {
             product_name: reader.read_string()?,
             script_defines: reader.read_string()?,
             global_dispatch_url_list: {
-                let mut buf = [0; 3]; // TODO!
-                reader.read_exact(&mut buf)?;
-                (0..reader.read_u32_varint()?)
-                    .filter_map(|_| reader.read_string().ok())
-                    .collect()
+                let list_start = reader.position();
+                let urls = read_url_list(&mut reader)
+                    .map(validate_urls)
+                    .unwrap_or_default();
+                if urls.is_empty() {
+                    log::warn!(
+                        "Unexpected dispatch URL list framing in ClientConfig, scanning for URLs instead"
+                    );
+                    dedup_urls(scan_url_list(&mut reader, list_start))
+                } else {
+                    dedup_urls(urls)
+                }
             },
         })
     }
 }
+
+/// Reads the dispatch URL list with the framing of current clients: 3 unknown bytes, then a varint count of strings.
+fn read_url_list(reader: &mut Cursor<Vec<u8>>) -> std::io::Result<Vec<String>> {
+    let mut buf = [0; 3]; // TODO!
+    reader.read_exact(&mut buf)?;
+    Ok((0..reader.read_u32_varint()?)
+        .map_while(|_| reader.read_string().ok())
+        .collect())
+}
+
+/// Looks for the first offset from `start` where a run of strings parsing as http(s) URLs begins, and returns that run.
+/// Used when the framing before the list changed and `read_url_list` reads garbage.
+fn scan_url_list(reader: &mut Cursor<Vec<u8>>, start: u64) -> Vec<String> {
+    let end = reader.get_ref().len() as u64;
+    for offset in start..end {
+        reader.set_position(offset);
+        let urls: Vec<String> = std::iter::from_fn(|| reader.read_string().ok())
+            .take_while(|url| is_http_url(url))
+            .collect();
+        if !urls.is_empty() {
+            return urls;
+        }
+    }
+
+    Vec::new()
+}
+
+/// Returns whether `url` is an absolute http(s) URL.
+fn is_http_url(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .is_ok_and(|parsed| matches!(parsed.scheme(), "http" | "https") && parsed.has_host())
+}
+
+/// Drops every entry that is not an absolute http(s) URL, which usually means the list was read from the wrong offset.
+fn validate_urls(urls: Vec<String>) -> Vec<String> {
+    urls.into_iter()
+        .filter(|url| {
+            let valid = is_http_url(url);
+            if !valid {
+                log::warn!("Ignoring invalid dispatch URL in ClientConfig: {url:?}");
+            }
+            valid
+        })
+        .collect()
+}
+
+/// Removes repeated URLs from the list while keeping the first occurrence of each in order.
+fn dedup_urls(urls: Vec<String>) -> Vec<String> {
+    let total = urls.len();
+    let mut seen = HashSet::new();
+    let urls: Vec<String> = urls
+        .into_iter()
+        .filter(|url| seen.insert(url.clone()))
+        .collect();
+
+    if urls.len() < total {
+        log::info!(
+            "Removed {} duplicate dispatch URL(s) from ClientConfig",
+            total - urls.len()
+        );
+    }
+
+    urls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::capture_logs;
+
+    /// Encodes `s` the way ClientConfig strings are stored: a flag byte, a varint length, then the bytes.
+    fn string(s: &str) -> Vec<u8> {
+        let mut buf = vec![1, s.len() as u8];
+        buf.extend_from_slice(s.as_bytes());
+        buf
+    }
+
+    /// Builds a ClientConfig file listing `urls`, with `header` between the leading strings and the list count.
+    fn client_config(header: &[u8], urls: &[&str]) -> Vec<u8> {
+        let mut buf = ["CNPRODWin", "com.miHoYo.hkrpg", "StarRail", "RELEASE"]
+            .into_iter()
+            .flat_map(string)
+            .collect::<Vec<_>>();
+        buf.extend_from_slice(header);
+        buf.push(urls.len() as u8);
+        buf.extend(urls.iter().flat_map(|url| string(url)));
+        buf
+    }
+
+    #[test]
+    fn removes_duplicate_dispatch_urls() {
+        let data = client_config(
+            &[0; 3],
+            &[
+                "https://globaldp-prod-cn01.bhsr.com/query_dispatch",
+                "https://globaldp-prod-cn02.bhsr.com/query_dispatch",
+                "https://globaldp-prod-cn01.bhsr.com/query_dispatch",
+            ],
+        );
+
+        let mut config = None;
+        let logs = capture_logs(|| config = Some(ClientStartupConfig::try_from(data).unwrap()));
+
+        assert_eq!(
+            config.unwrap().global_dispatch_url_list,
+            [
+                "https://globaldp-prod-cn01.bhsr.com/query_dispatch",
+                "https://globaldp-prod-cn02.bhsr.com/query_dispatch",
+            ]
+        );
+        assert!(logs.contains(&String::from(
+            "INFO Removed 1 duplicate dispatch URL(s) from ClientConfig"
+        )));
+    }
+
+    #[test]
+    fn drops_invalid_dispatch_urls() {
+        let data = client_config(
+            &[0; 3],
+            &[
+                "https://globaldp-prod-cn01.bhsr.com/query_dispatch",
+                "\u{1}\u{2}garbage",
+            ],
+        );
+
+        let mut config = None;
+        let logs = capture_logs(|| config = Some(ClientStartupConfig::try_from(data).unwrap()));
+
+        assert_eq!(
+            config.unwrap().global_dispatch_url_list,
+            ["https://globaldp-prod-cn01.bhsr.com/query_dispatch"]
+        );
+        assert!(logs.contains(&String::from(
+            "WARN Ignoring invalid dispatch URL in ClientConfig: \"\\u{1}\\u{2}garbage\""
+        )));
+    }
+
+    #[test]
+    fn accepts_only_absolute_http_urls() {
+        assert!(is_http_url("http://127.0.0.1:21000/query_dispatch"));
+        assert!(is_http_url(
+            "https://globaldp-prod-cn01.bhsr.com/query_dispatch"
+        ));
+        assert!(!is_http_url("/query_dispatch"));
+        assert!(!is_http_url("file:///tmp/dispatch.b64"));
+    }
+
+    const URLS: [&str; 2] = [
+        "https://globaldp-prod-cn01.bhsr.com/query_dispatch",
+        "https://globaldp-prod-cn02.bhsr.com/query_dispatch",
+    ];
+
+    #[test]
+    fn reads_the_current_url_list_framing() {
+        let mut config = None;
+        let logs = capture_logs(|| {
+            config = Some(ClientStartupConfig::try_from(client_config(&[0; 3], &URLS)).unwrap())
+        });
+
+        let config = config.unwrap();
+        assert_eq!(config.channel_name, "CNPRODWin");
+        assert_eq!(config.global_dispatch_url_list, URLS);
+        assert!(logs.iter().all(|log| !log.starts_with("WARN")));
+    }
+
+    #[test]
+    fn scans_for_the_url_list_when_the_framing_shifted() {
+        let mut config = None;
+        let logs = capture_logs(|| {
+            config = Some(ClientStartupConfig::try_from(client_config(&[0; 5], &URLS)).unwrap())
+        });
+
+        assert_eq!(config.unwrap().global_dispatch_url_list, URLS);
+        assert!(logs.contains(&String::from(
+            "WARN Unexpected dispatch URL list framing in ClientConfig, scanning for URLs instead"
+        )));
+    }
+}
@@ -0,0 +1,76 @@
+use fetch_hotfix::{language::Language, platform::Platform};
+use serde::Deserialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Config file read from the current directory when `--config` isn't passed.
+pub const DEFAULT_CONFIG_PATH: &str = "fetch-hotfix.toml";
+
+/// Default values for the command line options, read from a TOML file.
+/// Every key is optional and named like the matching option, e.g. `channel_id` for `--channel-id`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub platform: Option<Platform>,
+    pub language: Option<Language>,
+    pub language_type: Option<u32>,
+    pub gateway_platform_type: Option<u32>,
+    pub channel_id: Option<u32>,
+    pub sub_channel_id: Option<u32>,
+    pub deadline: Option<u64>,
+    pub dispatch_timeout: Option<u64>,
+    pub gateway_timeout: Option<u64>,
+    pub out_dir: Option<PathBuf>,
+}
+
+impl Config {
+    /// Reads the config file at `path`.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        toml::from_str(&fs::read_to_string(path)?).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid config file {}: {e}", path.display()),
+            )
+        })
+    }
+
+    /// Reads `fetch-hotfix.toml` from the current directory, or returns an empty config if there is none.
+    pub fn read_default() -> io::Result<Self> {
+        match Self::read(Path::new(DEFAULT_CONFIG_PATH)) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DEFAULT_CONFIG_PATH);
+        fs::write(&path, "platform = \"ios\"\nsub_channel_id = 2\n").unwrap();
+
+        let config = Config::read(&path).unwrap();
+
+        assert_eq!(config.platform, Some(Platform::Ios));
+        assert_eq!(config.sub_channel_id, Some(2));
+        assert_eq!(config.language, None);
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DEFAULT_CONFIG_PATH);
+        fs::write(&path, "platfrom = \"ios\"\n").unwrap();
+
+        let error = Config::read(&path).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("unknown field `platfrom`"));
+    }
+}
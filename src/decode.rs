@@ -1,8 +1,56 @@
+use crate::base64;
+use chrono::{DateTime, Utc};
+use std::{collections::BTreeMap, ops::ControlFlow};
+
+/// Smallest absolute epoch value treated as milliseconds rather than seconds by `Decoded::as_timestamp`.
+/// As seconds it lies past the year 5000, as milliseconds it is in 1973.
+const EPOCH_MILLIS_THRESHOLD: u64 = 100_000_000_000;
+
+/// Largest field number allowed by protobuf.
+const MAX_FIELD_NUMBER: u32 = (1 << 29) - 1;
+
+/// How many bytes `Decoder::decode_best_effort` scans for the next field after a failure before giving up.
+const MAX_RESYNC_DISTANCE: usize = 4096;
+
+/// Default `DecoderOptions::max_depth`, the same recursion limit as prost.
+pub const DEFAULT_MAX_DEPTH: usize = 100;
+
 /// A decoder responsible for parsing raw byte data into structured information.
 #[derive(Debug)]
 pub struct Decoder {
     data: Vec<u8>,
     idx: usize,
+    options: DecoderOptions,
+    /// Number of messages this one is nested in.
+    depth: usize,
+}
+
+/// Settings controlling how a `Decoder` interprets ambiguous data.
+#[derive(Debug, Clone, Copy)]
+pub struct DecoderOptions {
+    /// Keep the successfully decoded prefix of length-delimited fields that only partially decode
+    /// as a nested message (`DecodedValue::PartialNested`), instead of falling back to a raw buffer.
+    pub keep_partial: bool,
+    /// Try to decode length-delimited fields as nested messages. When unset, they are all kept as raw buffers.
+    pub auto_nest: bool,
+    /// Fail with `DecodeError::AmbiguousField` on length-delimited fields that are neither a nested message
+    /// nor a UTF-8 string, instead of keeping them as raw buffers. Fields that aren't tried as nested messages,
+    /// because of `auto_nest` or `max_depth`, must be UTF-8 strings.
+    pub strict: bool,
+    /// Maximum number of nested messages. Length-delimited fields deeper than that are kept as raw buffers,
+    /// so that deeply nested input can't overflow the stack.
+    pub max_depth: usize,
+}
+
+impl Default for DecoderOptions {
+    fn default() -> Self {
+        Self {
+            keep_partial: false,
+            auto_nest: true,
+            strict: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
 }
 
 /// Errors that can occur during the decoding process.
@@ -12,10 +60,41 @@ pub enum DecodeError {
     UnsupportedWireType(u8),
     #[error("Invalid memory access detected")]
     InvalidMemoryAccess,
+    #[error("Malformed varint")]
+    MalformedVarint,
+    #[error("Maximum field count exceeded")]
+    FieldLimitExceeded,
+    #[error("Field {0} is neither a message nor a UTF-8 string")]
+    AmbiguousField(u32),
+    #[error("Invalid field number: {0}")]
+    InvalidFieldNumber(u128),
+}
+
+/// A decoding failure along with every top-level field decoded before it, see `Decoder::decode_partial`.
+#[derive(Debug, thiserror::Error)]
+#[error("{error} (after {} decoded fields)", partial.fields.len())]
+pub struct PartialDecodeError {
+    #[source]
+    pub error: DecodeError,
+    /// The fields decoded before the failure. `unprocessed` holds the data from the start of the failing field.
+    pub partial: DecodingResult,
+}
+
+/// A top-level field that failed to decode and was skipped by `Decoder::decode_best_effort`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+pub struct ErrorField {
+    /// Offset of the failing field in the data stream.
+    pub field_start: usize,
+    /// Offset at which decoding resumed. The bytes from `field_start` up to it were skipped.
+    pub field_end: usize,
+    /// Why the field failed to decode.
+    pub reason: String,
 }
 
 /// Represents the type of wire format for a field in the decoding process.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 pub enum WireType {
     /// Variable-length integer (varint).
     VarInt = 0,
@@ -33,6 +112,7 @@ pub enum WireType {
 
 /// Contains the decoded field information from a decoding operation.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 pub struct Decoded {
     /// The field number (e.g., in Protobuf, the field number).
     pub field: u32,
@@ -45,25 +125,43 @@ pub struct Decoded {
 }
 
 /// Enum representing different types of decoded values.
+/// When serialized, buffers are written as base64 strings.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 pub enum DecodedValue {
-    /// A decoded BigInt (i128).
-    BigInt(i128),
+    /// A decoded varint, read as unsigned like protobuf's `uint64`, see `Decoder::next_uvarint`.
+    UInt(u128),
     /// A decoded buffer (raw bytes).
-    Buffer(Vec<u8>),
+    Buffer(#[cfg_attr(feature = "serialize", serde(serialize_with = "serialize_base64"))] Vec<u8>),
     /// A decoded nested object.
     Nested(DecodingResult),
+    /// A nested object that could only be decoded up to some point, see `DecoderOptions::keep_partial`.
+    PartialNested {
+        /// The fields decoded before the failure.
+        fields: Vec<Decoded>,
+        /// The raw bytes starting at the field that failed to decode.
+        #[cfg_attr(feature = "serialize", serde(serialize_with = "serialize_base64"))]
+        trailing: Vec<u8>,
+    },
 }
 
 /// The result of decoding a structure, including both fields and unprocessed data.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 pub struct DecodingResult {
     /// A vector of decoded fields.
     pub fields: Vec<Decoded>,
     /// Any unprocessed bytes after decoding.
+    #[cfg_attr(feature = "serialize", serde(serialize_with = "serialize_base64"))]
     pub unprocessed: Vec<u8>,
 }
 
+/// Serializes raw bytes as a base64 string.
+#[cfg(feature = "serialize")]
+fn serialize_base64<S: serde::Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&base64::encode(bytes))
+}
+
 /// Represents the result of a simplified decoding process.
 #[derive(Debug, Clone)]
 pub struct SimpleDecodingResult {
@@ -91,12 +189,30 @@ pub enum SimpleDecodedValue {
     String(String),
     /// A simplified nested decoding result.
     Nested(SimpleDecodingResult),
+    /// A fixed-width integer (`i32` or `i64` wire type). The wire doesn't say whether it is signed,
+    /// so both interpretations are kept.
+    Fixed {
+        wire_type: &'static str,
+        signed: i64,
+        unsigned: u64,
+    },
 }
 
 impl Decoder {
     /// Creates a new `Decoder` instance with the given data.
     pub fn new(data: Vec<u8>) -> Self {
-        Self { data, idx: 0 }
+        Self::with_options(data, DecoderOptions::default())
+    }
+
+    /// Creates a new `Decoder` instance with the given data and options.
+    /// Nested messages are decoded with the same options.
+    pub fn with_options(data: Vec<u8>, options: DecoderOptions) -> Self {
+        Self {
+            data,
+            idx: 0,
+            options,
+            depth: 0,
+        }
     }
 
     /// Reads the next byte from the data stream, advancing the index.
@@ -104,25 +220,31 @@ impl Decoder {
         self.data
             .get(self.idx)
             .ok_or(DecodeError::InvalidMemoryAccess)
-            .map(|byte| {
-                self.idx += 1;
-                byte
-            })
+            .inspect(|_| self.idx += 1)
     }
 
     /// Reads the next variable-length integer (varint) from the data stream.
+    /// Varints longer than 18 bytes wrap into the sign bit, use `next_uvarint` for unsigned semantics.
     pub fn next_varint(&mut self) -> Result<i128, DecodeError> {
-        let mut value = 0_i128;
+        self.next_uvarint().map(|value| value as i128)
+    }
+
+    /// Reads the next variable-length integer (varint) from the data stream as an unsigned value.
+    pub fn next_uvarint(&mut self) -> Result<u128, DecodeError> {
+        let mut value = 0_u128;
         let mut shift = 0;
 
         loop {
             let byte = self.next_byte()?;
-            let current = (byte & 0x7F) as i128;
+            let current = (byte & 0x7F) as u128;
             value |= current << shift;
             if byte & 0x80 == 0 {
                 break;
             }
             shift += 7;
+            if shift >= u128::BITS {
+                return Err(DecodeError::MalformedVarint);
+            }
         }
 
         Ok(value)
@@ -131,7 +253,7 @@ impl Decoder {
     /// Reads a specific number of bytes from the data stream.
     pub fn read(&mut self, length: usize) -> Result<Vec<u8>, DecodeError> {
         self.data
-            .get(self.idx..self.idx + length)
+            .get(self.idx..self.idx.saturating_add(length))
             .map(|slice| {
                 self.idx += length;
                 slice.to_vec()
@@ -146,48 +268,291 @@ impl Decoder {
 
     /// Decodes the entire data stream into a `DecodingResult`.
     pub fn decode(&mut self) -> Result<DecodingResult, DecodeError> {
+        let mut budget = usize::MAX;
+        self.decode_budgeted(&mut budget)
+    }
+
+    /// Decodes the entire data stream like `decode`, but keeps the fields decoded so far when it fails.
+    pub fn decode_partial(&mut self) -> Result<DecodingResult, PartialDecodeError> {
+        let mut budget = usize::MAX;
+        let mut fields = Vec::new();
+        let outcome = self.decode_fields(&mut budget, &mut fields);
+        let result = DecodingResult {
+            fields,
+            unprocessed: self.data.split_off(self.idx),
+        };
+
+        match outcome {
+            Ok(()) => Ok(result),
+            Err(error) => Err(PartialDecodeError {
+                error,
+                partial: result,
+            }),
+        }
+    }
+
+    /// Decodes the entire data stream like `decode`, but skips the top-level fields that fail to decode instead of failing.
+    /// Each failure is recorded as an `ErrorField`, and decoding resumes at the next offset that looks like the start of a field.
+    /// If none is found within `MAX_RESYNC_DISTANCE` bytes, the rest of the data stream is left in `unprocessed`.
+    pub fn decode_best_effort(&mut self) -> (DecodingResult, Vec<ErrorField>) {
+        let mut budget = usize::MAX;
         let mut fields = Vec::new();
+        let mut errors = Vec::new();
+        let mut unprocessed = Vec::new();
 
+        while let Err(error) = self.decode_fields(&mut budget, &mut fields) {
+            let field_start = self.idx;
+            let resynced = self.skip_to_next_field(field_start + 1);
+            errors.push(ErrorField {
+                field_start,
+                field_end: self.idx,
+                reason: error.to_string(),
+            });
+            if !resynced {
+                unprocessed = self.data[self.idx..].to_vec();
+                self.idx = self.data.len();
+                break;
+            }
+        }
+
+        let result = DecodingResult {
+            fields,
+            unprocessed,
+        };
+        (result, errors)
+    }
+
+    /// Decodes the entire data stream like `decode`, but fails with `DecodeError::FieldLimitExceeded`
+    /// once more than `max_fields` fields are produced. Fields of nested messages count towards the same limit.
+    pub fn decode_limited(&mut self, max_fields: usize) -> Result<DecodingResult, DecodeError> {
+        let mut budget = max_fields;
+        self.decode_budgeted(&mut budget)
+    }
+
+    /// Decodes the next top-level field, or returns `None` once the data stream is exhausted.
+    /// Allows consuming large payloads incrementally, see `progress`.
+    pub fn decode_single(&mut self) -> Result<Option<Decoded>, DecodeError> {
+        if self.remaining() == 0 {
+            return Ok(None);
+        }
+
+        let mut budget = usize::MAX;
+        self.decode_field(&mut budget).map(Some)
+    }
+
+    /// Decodes the top-level fields one by one, passing each to `f` instead of collecting them.
+    /// Stops as soon as `f` returns `ControlFlow::Break`, leaving the rest of the data stream undecoded.
+    pub fn decode_with<B>(
+        &mut self,
+        mut f: impl FnMut(&Decoded) -> ControlFlow<B>,
+    ) -> Result<ControlFlow<B>, DecodeError> {
+        while let Some(field) = self.decode_single()? {
+            if let ControlFlow::Break(value) = f(&field) {
+                return Ok(ControlFlow::Break(value));
+            }
+        }
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Decodes the data stream as consecutive messages, each prefixed with its length as a varint
+    /// (the framing of protobuf's `writeDelimitedTo`). Every message is decoded on its own.
+    pub fn decode_messages(&mut self) -> Result<Vec<DecodingResult>, DecodeError> {
+        let mut messages = Vec::new();
+        while self.remaining() > 0 {
+            let length = usize::try_from(self.next_uvarint()?)
+                .map_err(|_| DecodeError::InvalidMemoryAccess)?;
+            let message = self.read(length)?;
+            messages.push(Decoder::with_options(message, self.options).decode()?);
+        }
+
+        Ok(messages)
+    }
+
+    /// Returns `(consumed, total)`, the number of bytes decoded so far and the total size of the data stream.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.idx, self.data.len())
+    }
+
+    /// Decodes the entire data stream, consuming one unit of `budget` per produced field.
+    fn decode_budgeted(&mut self, budget: &mut usize) -> Result<DecodingResult, DecodeError> {
+        let mut fields = Vec::new();
+        self.decode_fields(budget, &mut fields)?;
+
+        let result = DecodingResult {
+            fields,
+            unprocessed: self.read(self.remaining())?,
+        };
+        debug_assert!(
+            result.validate(),
+            "is_object disagrees with the decoded value"
+        );
+        Ok(result)
+    }
+
+    /// Decodes fields into `fields` until the data stream is exhausted.
+    /// On failure, `fields` holds every field decoded so far and the position is reset to the start of the failing field.
+    fn decode_fields(
+        &mut self,
+        budget: &mut usize,
+        fields: &mut Vec<Decoded>,
+    ) -> Result<(), DecodeError> {
         while self.remaining() > 0 {
-            let enc = self.next_varint()? as u32;
-            let field = enc >> 3;
-            let wire_type = WireType::from_u8((enc & 7) as u8)?;
-
-            let mut value_decoded = false;
-            let value = match wire_type {
-                WireType::VarInt => DecodedValue::BigInt(self.next_varint()?),
-                WireType::Len => {
-                    let length = self.next_varint()? as usize;
-                    let sub_data = self.read(length)?;
-                    let mut nested_decoder = Decoder::new(sub_data.clone());
-                    match nested_decoder.decode() {
-                        Ok(decoded) => {
-                            value_decoded = true;
-                            DecodedValue::Nested(decoded)
+            let start = self.idx;
+            match self.decode_field(budget) {
+                Ok(field) => fields.push(field),
+                Err(err) => {
+                    self.idx = start;
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves to the first offset from `from` that looks like the start of a field, see `is_plausible_field`.
+    /// Only the next `MAX_RESYNC_DISTANCE` offsets are tried. Returns false if none of them matched,
+    /// leaving the position after the last offset tried.
+    fn skip_to_next_field(&mut self, from: usize) -> bool {
+        let end = from
+            .saturating_add(MAX_RESYNC_DISTANCE)
+            .min(self.data.len());
+        match (from..end).find(|&start| self.is_plausible_field(start)) {
+            Some(start) => {
+                self.idx = start;
+                true
+            }
+            None => {
+                self.idx = end;
+                // Reaching the end of the data stream isn't a failure to resync
+                self.idx == self.data.len()
+            }
+        }
+    }
+
+    /// Cheaply checks whether a field starts at `start`: the tag holds a valid field number and wire type,
+    /// and the value fits in the data stream. The value itself isn't decoded.
+    fn is_plausible_field(&mut self, start: usize) -> bool {
+        self.idx = start;
+        let Ok(enc) = self.next_uvarint() else {
+            return false;
+        };
+        if !u32::try_from(enc >> 3).is_ok_and(|field| (1..=MAX_FIELD_NUMBER).contains(&field)) {
+            return false;
+        }
+
+        let length = match WireType::from_u8((enc & 7) as u8) {
+            Ok(WireType::VarInt) => return self.next_uvarint().is_ok(),
+            Ok(WireType::Len) => match self.next_uvarint().map(usize::try_from) {
+                Ok(Ok(length)) => length,
+                _ => return false,
+            },
+            Ok(WireType::I32) => 4,
+            Ok(WireType::I64) => 8,
+            _ => return false,
+        };
+        length <= self.remaining()
+    }
+
+    /// Decodes a single field at the current position, consuming one unit of `budget` for it
+    /// and for each field of its nested messages.
+    /// The unit is only consumed once the field has decoded, so `DecodeError::FieldLimitExceeded` is never
+    /// returned for data that isn't a field.
+    fn decode_field(&mut self, budget: &mut usize) -> Result<Decoded, DecodeError> {
+        let enc = self.next_uvarint()?;
+        let field = u32::try_from(enc >> 3)
+            .ok()
+            .filter(|field| (1..=MAX_FIELD_NUMBER).contains(field))
+            .ok_or(DecodeError::InvalidFieldNumber(enc >> 3))?;
+        let wire_type = WireType::from_u8((enc & 7) as u8)?;
+
+        let mut value_decoded = false;
+        let value = match wire_type {
+            WireType::VarInt => DecodedValue::UInt(self.next_uvarint()?),
+            WireType::Len => {
+                let length = usize::try_from(self.next_uvarint()?)
+                    .map_err(|_| DecodeError::InvalidMemoryAccess)?;
+                let sub_data = self.read(length)?;
+                // Keep the payload as bytes when nesting is disabled or too deep, or when it is empty,
+                // which is as much an empty string as an empty message
+                if sub_data.is_empty()
+                    || !self.options.auto_nest
+                    || self.depth >= self.options.max_depth
+                {
+                    if self.options.strict && std::str::from_utf8(&sub_data).is_err() {
+                        return Err(DecodeError::AmbiguousField(field));
+                    }
+                    consume(budget)?;
+                    return Ok(Decoded {
+                        field,
+                        wire_type,
+                        is_object: false,
+                        value: DecodedValue::Buffer(sub_data),
+                    });
+                }
+                let mut nested_decoder = Decoder::with_options(sub_data.clone(), self.options);
+                nested_decoder.depth = self.depth + 1;
+                let mut nested_fields = Vec::new();
+                // The payload is only tentatively a message, so its fields are counted against a copy of the budget
+                // that is kept only if they are
+                let mut nested_budget = *budget;
+                match nested_decoder.decode_fields(&mut nested_budget, &mut nested_fields) {
+                    Ok(()) => {
+                        *budget = nested_budget;
+                        value_decoded = true;
+                        DecodedValue::Nested(DecodingResult {
+                            fields: nested_fields,
+                            unprocessed: Vec::new(),
+                        })
+                    }
+                    // Running out of budget inside a message is a failure of the whole decode,
+                    // not a sign that the payload isn't a message
+                    Err(DecodeError::FieldLimitExceeded) => {
+                        return Err(DecodeError::FieldLimitExceeded);
+                    }
+                    // A nested field that is itself ambiguous is the one to report
+                    Err(err @ DecodeError::AmbiguousField(_))
+                        if std::str::from_utf8(&sub_data).is_err() =>
+                    {
+                        return Err(err);
+                    }
+                    Err(_) if self.options.strict && std::str::from_utf8(&sub_data).is_err() => {
+                        return Err(DecodeError::AmbiguousField(field));
+                    }
+                    Err(_) if self.options.keep_partial && !nested_fields.is_empty() => {
+                        *budget = nested_budget;
+                        DecodedValue::PartialNested {
+                            fields: nested_fields,
+                            trailing: nested_decoder.data.split_off(nested_decoder.idx),
                         }
-                        Err(_) => DecodedValue::Buffer(sub_data),
                     }
+                    Err(_) => DecodedValue::Buffer(sub_data),
                 }
-                WireType::I32 => DecodedValue::Buffer(self.read(4)?),
-                WireType::I64 => DecodedValue::Buffer(self.read(8)?),
-                _ => return Err(DecodeError::UnsupportedWireType((enc & 7) as u8)),
-            };
-
-            fields.push(Decoded {
-                field: field,
-                wire_type: wire_type,
-                is_object: value_decoded,
-                value: value,
-            });
-        }
+            }
+            WireType::I32 => DecodedValue::Buffer(self.read(4)?),
+            WireType::I64 => DecodedValue::Buffer(self.read(8)?),
+            _ => return Err(DecodeError::UnsupportedWireType((enc & 7) as u8)),
+        };
 
-        Ok(DecodingResult {
-            fields: fields,
-            unprocessed: self.read(self.remaining())?,
+        consume(budget)?;
+        Ok(Decoded {
+            field,
+            wire_type,
+            is_object: value_decoded,
+            value,
         })
     }
 }
 
+/// Consumes one unit of `budget`, failing with `DecodeError::FieldLimitExceeded` if there is none left.
+fn consume(budget: &mut usize) -> Result<(), DecodeError> {
+    *budget = budget
+        .checked_sub(1)
+        .ok_or(DecodeError::FieldLimitExceeded)?;
+    Ok(())
+}
+
 impl WireType {
     /// Converts a raw `u8` value into a `WireType` enum variant.
     /// Returns an error if the value does not correspond to a valid `WireType`.
@@ -200,9 +565,97 @@ impl WireType {
             _ => Err(DecodeError::UnsupportedWireType(value)),
         }
     }
+
+    /// Returns the lowercase name of the wire type, as used in exported output.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WireType::VarInt => "varint",
+            WireType::I64 => "i64",
+            WireType::Len => "len",
+            WireType::SGroup => "sgroup",
+            WireType::EGroup => "egroup",
+            WireType::I32 => "i32",
+        }
+    }
+}
+
+impl Decoded {
+    /// Converts the field into a JSON object holding its field number, wire type and value.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "field": self.field,
+            "wire_type": self.wire_type.as_str(),
+            "value": self.value.to_json(),
+        })
+    }
+
+    /// Interprets a fixed64 field as a signed Unix timestamp, in seconds or in milliseconds depending on its magnitude.
+    /// Returns `None` for any other kind of field, or if the timestamp is out of range.
+    pub fn as_timestamp(&self) -> Option<DateTime<Utc>> {
+        let DecodedValue::Buffer(buf) = &self.value else {
+            return None;
+        };
+        if self.wire_type != WireType::I64 {
+            return None;
+        }
+
+        let epoch = i64::from_le_bytes(buf.as_slice().try_into().ok()?);
+        if epoch.unsigned_abs() >= EPOCH_MILLIS_THRESHOLD {
+            DateTime::from_timestamp_millis(epoch)
+        } else {
+            DateTime::from_timestamp(epoch, 0)
+        }
+    }
 }
 
 impl DecodedValue {
+    /// Converts the value into JSON.
+    /// Integers outside of the 64-bit range are written as strings, buffers are written as UTF-8 strings when possible
+    /// and as base64 otherwise, and nested objects become arrays of fields.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            DecodedValue::UInt(num) => u64::try_from(*num)
+                .map(serde_json::Value::from)
+                .unwrap_or_else(|_| serde_json::Value::from(num.to_string())),
+            DecodedValue::Buffer(buf) => match std::str::from_utf8(buf) {
+                Ok(s) => serde_json::Value::from(s),
+                Err(_) => serde_json::Value::from(base64::encode(buf)),
+            },
+            DecodedValue::Nested(nested) => {
+                serde_json::Value::from_iter(nested.fields.iter().map(Decoded::to_json))
+            }
+            DecodedValue::PartialNested { fields, trailing } => serde_json::json!({
+                "fields": serde_json::Value::from_iter(fields.iter().map(Decoded::to_json)),
+                "trailing": base64::encode(trailing),
+            }),
+        }
+    }
+
+    /// Returns a short, single-line representation of the value, at most `max_chars` characters long
+    /// (plus an ellipsis when truncated).
+    pub fn preview(&self, max_chars: usize) -> String {
+        let full = match self {
+            DecodedValue::UInt(num) => num.to_string(),
+            DecodedValue::Buffer(buf) => match std::str::from_utf8(buf) {
+                Ok(s) => s.to_string(),
+                Err(_) => base64::encode(buf),
+            },
+            DecodedValue::Nested(nested) => format!("<{} nested fields>", nested.fields.len()),
+            DecodedValue::PartialNested { fields, trailing } => format!(
+                "<{} nested fields, {} trailing bytes>",
+                fields.len(),
+                trailing.len()
+            ),
+        };
+        let full = full.replace(['\r', '\n'], " ");
+
+        if full.chars().count() > max_chars {
+            full.chars().take(max_chars).chain("...".chars()).collect()
+        } else {
+            full
+        }
+    }
+
     /// Unwraps a `DecodedValue::Nested` variant into the underlying `DecodingResult`.
     /// Panics if the value is not a `Nested` variant.
     fn unwrap_nested(self) -> DecodingResult {
@@ -214,7 +667,111 @@ impl DecodedValue {
     }
 }
 
+/// Appends `value` to `buf` as a variable-length integer (varint).
+fn write_varint(buf: &mut Vec<u8>, mut value: u128) {
+    while value >= 0x80 {
+        buf.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
 impl DecodingResult {
+    /// Renders the field tree as a Graphviz DOT digraph: one node per field, labeled with its number and wire type,
+    /// with edges from each message to its fields.
+    pub fn to_dot(&self) -> String {
+        fn add_fields(fields: &[Decoded], parent: &str, next_id: &mut usize, dot: &mut String) {
+            for field in fields {
+                let node = format!("f{next_id}");
+                *next_id += 1;
+                *dot += &format!(
+                    "\t{node} [label=\"#{} {}\"];\n\t{parent} -> {node};\n",
+                    field.field,
+                    field.wire_type.as_str()
+                );
+
+                match &field.value {
+                    DecodedValue::Nested(nested) => add_fields(&nested.fields, &node, next_id, dot),
+                    DecodedValue::PartialNested { fields, .. } => {
+                        add_fields(fields, &node, next_id, dot)
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut dot = String::from("digraph DecodingResult {\n\troot [label=\"message\"];\n");
+        add_fields(&self.fields, "root", &mut 0, &mut dot);
+        dot += "}\n";
+        dot
+    }
+
+    /// Groups the top-level fields by field number, keeping repeated fields in their decoding order.
+    pub fn fields_by_number(&self) -> BTreeMap<u32, Vec<&Decoded>> {
+        let mut fields: BTreeMap<u32, Vec<&Decoded>> = BTreeMap::new();
+        for field in &self.fields {
+            fields.entry(field.field).or_default().push(field);
+        }
+        fields
+    }
+
+    /// Counts the top-level fields of each wire type, keyed by `WireType::as_str`.
+    pub fn wire_type_counts(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for field in &self.fields {
+            *counts.entry(field.wire_type.as_str()).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Checks that `is_object` is set exactly for the fields holding a nested message, at every level.
+    pub fn validate(&self) -> bool {
+        self.fields.iter().all(|field| match &field.value {
+            DecodedValue::Nested(nested) => field.is_object && nested.validate(),
+            _ => !field.is_object,
+        })
+    }
+
+    /// Encodes the result back into its wire format, followed by the unprocessed bytes.
+    /// Decoding the output yields the same fields, as long as every buffer is valid for its wire type.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+
+        for field in &self.fields {
+            write_varint(
+                &mut buf,
+                ((field.field as u128) << 3) | field.wire_type as u128,
+            );
+
+            match &field.value {
+                DecodedValue::UInt(num) => write_varint(&mut buf, *num),
+                DecodedValue::Buffer(bytes) => {
+                    if field.wire_type == WireType::Len {
+                        write_varint(&mut buf, bytes.len() as u128);
+                    }
+                    buf.extend_from_slice(bytes);
+                }
+                DecodedValue::Nested(nested) => {
+                    let bytes = nested.encode();
+                    write_varint(&mut buf, bytes.len() as u128);
+                    buf.extend_from_slice(&bytes);
+                }
+                DecodedValue::PartialNested { fields, trailing } => {
+                    let bytes = DecodingResult {
+                        fields: fields.clone(),
+                        unprocessed: trailing.clone(),
+                    }
+                    .encode();
+                    write_varint(&mut buf, bytes.len() as u128);
+                    buf.extend_from_slice(&bytes);
+                }
+            }
+        }
+
+        buf.extend_from_slice(&self.unprocessed);
+        buf
+    }
+
     /// Simplifies the decoding result into a `SimpleDecodingResult`.
     /// Converts the decoded fields into a simpler format, with nested objects being recursively simplified.
     pub fn simplify(self) -> SimpleDecodingResult {
@@ -234,17 +791,34 @@ impl DecodingResult {
                 .into_iter()
                 .map(|field| {
                     // let wire_type = wire_type_to_str(field.wire_type);
-                    let value = if field.is_object {
-                        SimpleDecodedValue::Nested(field.value.unwrap_nested().simplify())
-                    } else {
-                        SimpleDecodedValue::String(format!("{:?}", field.value))
+                    let value = match (&field.value, field.wire_type) {
+                        _ if field.is_object => {
+                            SimpleDecodedValue::Nested(field.value.unwrap_nested().simplify())
+                        }
+                        (DecodedValue::Buffer(buf), WireType::I32) if buf.len() == 4 => {
+                            let unsigned = u32::from_le_bytes(buf.as_slice().try_into().unwrap());
+                            SimpleDecodedValue::Fixed {
+                                wire_type: WireType::I32.as_str(),
+                                signed: unsigned as i32 as i64,
+                                unsigned: unsigned as u64,
+                            }
+                        }
+                        (DecodedValue::Buffer(buf), WireType::I64) if buf.len() == 8 => {
+                            let unsigned = u64::from_le_bytes(buf.as_slice().try_into().unwrap());
+                            SimpleDecodedValue::Fixed {
+                                wire_type: WireType::I64.as_str(),
+                                signed: unsigned as i64,
+                                unsigned,
+                            }
+                        }
+                        _ => SimpleDecodedValue::String(format!("{:?}", field.value)),
                     };
 
                     SimpleDecoded {
                         field: field.field,
                         wire_type: String::with_capacity(0),
                         is_object: field.is_object,
-                        value: value,
+                        value,
                     }
                 })
                 .collect(),
@@ -275,6 +849,555 @@ impl std::fmt::Display for SimpleDecodedValue {
                 write!(f, "{}", parse_buffer(s).unwrap_or(String::from(s)))
             }
             SimpleDecodedValue::Nested(nested) => write!(f, "{:?}", nested),
+            SimpleDecodedValue::Fixed {
+                wire_type,
+                signed,
+                unsigned,
+            } => write!(
+                f,
+                "{{\"{wire_type}\": {{\"signed\": {signed}, \"unsigned\": {unsigned}}}}}"
+            ),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Field 1 as a varint holding 1.
+    const VARINT_FIELD: [u8; 2] = [0x08, 0x01];
+
+    /// Returns field 2 as a length-delimited field holding `payload`.
+    fn len_field(payload: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0x12, payload.len() as u8];
+        buf.extend_from_slice(payload);
+        buf
+    }
+
+    #[test]
+    fn decode_limited_fails_past_the_limit() {
+        let data = VARINT_FIELD.repeat(3);
+
+        assert!(matches!(
+            Decoder::new(data.clone()).decode_limited(2),
+            Err(DecodeError::FieldLimitExceeded)
+        ));
+        assert_eq!(
+            Decoder::new(data).decode_limited(3).unwrap().fields.len(),
+            3
+        );
+    }
+
+    #[test]
+    fn decode_limited_counts_nested_fields() {
+        let data = len_field(&VARINT_FIELD.repeat(2));
+
+        let result = Decoder::new(data.clone()).decode_limited(3).unwrap();
+        assert!(matches!(result.fields[0].value, DecodedValue::Nested(_)));
+
+        // The nested message decodes, but its fields and itself don't fit in the budget
+        assert!(matches!(
+            Decoder::new(data).decode_limited(2),
+            Err(DecodeError::FieldLimitExceeded)
+        ));
+    }
+
+    #[test]
+    fn decode_limited_refunds_failed_nested_attempts() {
+        // The payload decodes as a field before failing, which must not use up the budget of the next field
+        let mut data = len_field(&[0x08, 0x01, 0xFF]);
+        data.extend_from_slice(&VARINT_FIELD);
+
+        let result = Decoder::new(data).decode_limited(2).unwrap();
+        assert_eq!(result.fields.len(), 2);
+    }
+
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn serializes_buffers_as_base64() {
+        let mut data = len_field(&[0xFF, 0x00]);
+        data.extend_from_slice(&VARINT_FIELD);
+        let result = Decoder::new(data).decode().unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&result).unwrap(),
+            serde_json::json!({
+                "fields": [
+                    {"field": 2, "wire_type": "Len", "is_object": false, "value": {"Buffer": "/wA="}},
+                    {"field": 1, "wire_type": "VarInt", "is_object": false, "value": {"UInt": 1}},
+                ],
+                "unprocessed": "",
+            })
+        );
+    }
+
+    /// Returns `depth` length-delimited fields nested in each other around a varint field.
+    fn nested(depth: usize) -> Vec<u8> {
+        (0..depth).fold(VARINT_FIELD.to_vec(), |inner, _| {
+            let mut buf = vec![0x12];
+            write_varint(&mut buf, inner.len() as u128);
+            buf.extend_from_slice(&inner);
+            buf
+        })
+    }
+
+    /// Returns the number of nested messages decoded below `result`.
+    fn nesting_depth(result: &DecodingResult) -> usize {
+        match &result.fields[0].value {
+            DecodedValue::Nested(nested) => 1 + nesting_depth(nested),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn stops_nesting_at_max_depth() {
+        let options = DecoderOptions {
+            max_depth: 3,
+            ..Default::default()
+        };
+
+        let result = Decoder::with_options(nested(5), options).decode().unwrap();
+
+        assert_eq!(nesting_depth(&result), 3);
+        assert!(result.validate());
+    }
+
+    #[test]
+    fn decodes_deeply_nested_input_without_overflowing() {
+        let result = Decoder::new(nested(5000)).decode().unwrap();
+
+        assert_eq!(nesting_depth(&result), DEFAULT_MAX_DEPTH);
+    }
+
+    #[test]
+    fn progress_advances_after_each_field() {
+        let mut data = VARINT_FIELD.to_vec();
+        data.extend(len_field(b"abc"));
+        let mut decoder = Decoder::new(data);
+
+        assert_eq!(decoder.progress(), (0, 7));
+        decoder.decode_single().unwrap().unwrap();
+        assert_eq!(decoder.progress(), (2, 7));
+        assert_eq!(decoder.remaining(), 5);
+        decoder.decode_single().unwrap().unwrap();
+        assert_eq!(decoder.progress(), (7, 7));
+        assert!(decoder.decode_single().unwrap().is_none());
+    }
+
+    #[test]
+    fn keeps_the_decoded_prefix_of_partial_nested_messages() {
+        // Three valid fields, then a field with the unsupported wire type 7
+        let mut payload = VARINT_FIELD.repeat(3);
+        payload.extend_from_slice(&[0x0F, 0xAA]);
+        let data = len_field(&payload);
+        let options = DecoderOptions {
+            keep_partial: true,
+            ..Default::default()
+        };
+
+        let result = Decoder::with_options(data.clone(), options)
+            .decode()
+            .unwrap();
+        let DecodedValue::PartialNested { fields, trailing } = &result.fields[0].value else {
+            panic!("expected a partially decoded message");
+        };
+        assert_eq!(fields.len(), 3);
+        assert_eq!(trailing, &[0x0F, 0xAA]);
+        assert_eq!(result.encode(), data);
+
+        let result = Decoder::new(data).decode().unwrap();
+        assert!(matches!(&result.fields[0].value, DecodedValue::Buffer(buf) if *buf == payload));
+    }
+
+    #[test]
+    fn decodes_length_prefixed_messages_separately() {
+        let first = VARINT_FIELD.repeat(2);
+        let second = len_field(b"abc");
+        let mut data = vec![first.len() as u8];
+        data.extend_from_slice(&first);
+        data.push(second.len() as u8);
+        data.extend_from_slice(&second);
+
+        let messages = Decoder::new(data).decode_messages().unwrap();
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].fields.len(), 2);
+        assert_eq!(messages[0].encode(), first);
+        assert_eq!(messages[1].fields.len(), 1);
+        assert_eq!(messages[1].fields[0].field, 2);
+        assert_eq!(messages[1].encode(), second);
+    }
+
+    #[test]
+    fn fails_on_a_truncated_message() {
+        let data = [0x05, 0x08, 0x01];
+
+        assert!(Decoder::new(data.to_vec()).decode_messages().is_err());
+    }
+
+    #[test]
+    fn decode_with_stops_at_the_first_match() {
+        let mut data = VARINT_FIELD.to_vec();
+        data.extend(len_field(b"abc"));
+        data.extend(len_field(b"def"));
+        let mut decoder = Decoder::new(data);
+
+        let mut seen = 0;
+        let found = decoder
+            .decode_with(|decoded| {
+                seen += 1;
+                match decoded.field {
+                    2 => ControlFlow::Break(decoded.clone()),
+                    _ => ControlFlow::Continue(()),
+                }
+            })
+            .unwrap();
+
+        let ControlFlow::Break(found) = found else {
+            panic!("expected field 2 to be found");
+        };
+        assert!(matches!(&found.value, DecodedValue::Buffer(buf) if buf == b"abc"));
+        assert_eq!(seen, 2);
+        // The last field is left undecoded
+        assert_eq!(decoder.remaining(), 5);
+    }
+
+    #[test]
+    fn decode_partial_keeps_the_fields_before_the_failure() {
+        // Two valid fields, then a field with the unsupported wire type 7
+        let mut data = VARINT_FIELD.to_vec();
+        data.extend(len_field(b"abc"));
+        data.extend_from_slice(&[0x0F, 0xAA]);
+
+        let failure = Decoder::new(data).decode_partial().unwrap_err();
+
+        assert!(matches!(failure.error, DecodeError::UnsupportedWireType(7)));
+        assert_eq!(failure.partial.fields.len(), 2);
+        assert_eq!(failure.partial.fields[1].field, 2);
+        assert_eq!(failure.partial.unprocessed, [0x0F, 0xAA]);
+        assert_eq!(
+            failure.to_string(),
+            "Unsupported wire type: 7 (after 2 decoded fields)"
+        );
+    }
+
+    #[test]
+    fn validates_decoded_gateserver_messages() {
+        let result = Decoder::new(crate::test_util::gateserver_message())
+            .decode()
+            .unwrap();
+        assert!(result.validate());
+
+        let mut nested = Decoder::new(len_field(&VARINT_FIELD)).decode().unwrap();
+        assert!(nested.validate());
+        nested.fields[0].is_object = false;
+        assert!(!nested.validate());
+    }
+
+    #[test]
+    fn reads_unsigned_varints_above_i64_max() {
+        let mut data = Vec::new();
+        write_varint(&mut data, u64::MAX as u128);
+        write_varint(&mut data, i64::MAX as u128 + 1);
+        let mut decoder = Decoder::new(data);
+
+        assert_eq!(decoder.next_uvarint().unwrap(), u64::MAX as u128);
+        assert_eq!(decoder.next_uvarint().unwrap(), 1 << 63);
+        assert_eq!(decoder.remaining(), 0);
+    }
+
+    #[test]
+    fn rejects_lengths_that_do_not_fit_in_usize() {
+        // Field 2 as a length-delimited field of 2^70 bytes, which used to wrap to an empty buffer
+        let mut data = vec![0x12];
+        write_varint(&mut data, 1 << 70);
+
+        assert!(matches!(
+            Decoder::new(data.clone()).decode(),
+            Err(DecodeError::InvalidMemoryAccess)
+        ));
+        assert!(matches!(
+            Decoder::new(data[1..].to_vec()).decode_messages(),
+            Err(DecodeError::InvalidMemoryAccess)
+        ));
+    }
+
+    #[test]
+    fn writes_uint64_fields_as_unsigned_json_numbers() {
+        let mut data = vec![0x08];
+        write_varint(&mut data, u64::MAX as u128);
+
+        let result = Decoder::new(data).decode().unwrap();
+
+        assert!(
+            matches!(result.fields[0].value, DecodedValue::UInt(num) if num == u64::MAX as u128)
+        );
+        assert_eq!(
+            result.fields[0].value.to_json(),
+            serde_json::Value::from(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn simplifies_fixed_fields_as_signed_and_unsigned() {
+        // Field 1 as fixed32 and field 2 as fixed64, both with every bit set
+        let mut data = vec![0x0D, 0xFF, 0xFF, 0xFF, 0xFF, 0x11];
+        data.extend_from_slice(&[0xFF; 8]);
+
+        let simple = Decoder::new(data).decode().unwrap().simplify();
+
+        assert!(matches!(
+            simple.fields[0].value,
+            SimpleDecodedValue::Fixed {
+                wire_type: "i32",
+                signed: -1,
+                unsigned: 0xFFFF_FFFF,
+            }
+        ));
+        assert!(matches!(
+            simple.fields[1].value,
+            SimpleDecodedValue::Fixed {
+                wire_type: "i64",
+                signed: -1,
+                unsigned: u64::MAX,
+            }
+        ));
+        assert_eq!(
+            simple.fields[0].value.to_string(),
+            r#"{"i32": {"signed": -1, "unsigned": 4294967295}}"#
+        );
+    }
+
+    #[test]
+    fn dot_has_a_node_per_field() {
+        let mut data = len_field(&VARINT_FIELD.repeat(2));
+        data.extend_from_slice(&VARINT_FIELD);
+
+        let dot = Decoder::new(data).decode().unwrap().to_dot();
+
+        assert_eq!(
+            dot,
+            "digraph DecodingResult {\n\
+             \troot [label=\"message\"];\n\
+             \tf0 [label=\"#2 len\"];\n\troot -> f0;\n\
+             \tf1 [label=\"#1 varint\"];\n\tf0 -> f1;\n\
+             \tf2 [label=\"#1 varint\"];\n\tf0 -> f2;\n\
+             \tf3 [label=\"#1 varint\"];\n\troot -> f3;\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn empty_length_delimited_fields_are_empty_buffers() {
+        let mut data = len_field(&[]);
+        data.extend_from_slice(&VARINT_FIELD);
+
+        let result = Decoder::new(data.clone()).decode().unwrap();
+
+        assert_eq!(result.fields.len(), 2);
+        assert!(!result.fields[0].is_object);
+        assert!(matches!(&result.fields[0].value, DecodedValue::Buffer(buf) if buf.is_empty()));
+        assert!(result.validate());
+        assert_eq!(result.encode(), data);
+    }
+
+    #[test]
+    fn auto_nest_disabled_keeps_every_payload_as_bytes() {
+        let nested_payload = VARINT_FIELD.repeat(2);
+        let mut data = len_field(&nested_payload);
+        data.extend(len_field(b"text"));
+        let options = DecoderOptions {
+            auto_nest: false,
+            ..Default::default()
+        };
+
+        let result = Decoder::with_options(data, options).decode().unwrap();
+
+        assert!(result.fields.iter().all(|field| !field.is_object));
+        assert!(
+            matches!(&result.fields[0].value, DecodedValue::Buffer(buf) if *buf == nested_payload)
+        );
+        assert!(matches!(&result.fields[1].value, DecodedValue::Buffer(buf) if buf == b"text"));
+    }
+
+    #[test]
+    fn strict_mode_rejects_unclassifiable_payloads() {
+        let strict = DecoderOptions {
+            strict: true,
+            ..Default::default()
+        };
+
+        let data = len_field(&[0xFF, 0x00]);
+        assert!(matches!(
+            Decoder::with_options(data.clone(), strict).decode(),
+            Err(DecodeError::AmbiguousField(2))
+        ));
+        assert!(matches!(
+            &Decoder::new(data).decode().unwrap().fields[0].value,
+            DecodedValue::Buffer(buf) if buf == &[0xFF, 0x00]
+        ));
+
+        // Strings and messages are still accepted
+        let mut data = len_field(b"abc");
+        data.extend(len_field(&VARINT_FIELD));
+        let result = Decoder::with_options(data, strict).decode().unwrap();
+        assert!(matches!(&result.fields[0].value, DecodedValue::Buffer(buf) if buf == b"abc"));
+        assert!(matches!(result.fields[1].value, DecodedValue::Nested(_)));
+    }
+
+    #[test]
+    fn strict_mode_checks_fields_that_are_not_tried_as_messages() {
+        let data = len_field(&[0xFF, 0x00]);
+        for options in [
+            DecoderOptions {
+                strict: true,
+                auto_nest: false,
+                ..Default::default()
+            },
+            DecoderOptions {
+                strict: true,
+                max_depth: 0,
+                ..Default::default()
+            },
+        ] {
+            assert!(matches!(
+                Decoder::with_options(data.clone(), options).decode(),
+                Err(DecodeError::AmbiguousField(2))
+            ));
+        }
+    }
+
+    #[test]
+    fn strict_mode_reports_the_ambiguous_nested_field() {
+        let strict = DecoderOptions {
+            strict: true,
+            ..Default::default()
+        };
+        // Field 2 holding a message whose field 3 is ambiguous
+        let data = len_field(&[0x1A, 0x02, 0xFF, 0x00]);
+
+        assert!(matches!(
+            Decoder::with_options(data, strict).decode(),
+            Err(DecodeError::AmbiguousField(3))
+        ));
+    }
+
+    /// Decodes field 1 as a fixed64 holding `value`.
+    fn fixed64_field(value: i64) -> Decoded {
+        let mut data = vec![0x09];
+        data.extend_from_slice(&value.to_le_bytes());
+        Decoder::new(data).decode().unwrap().fields.remove(0)
+    }
+
+    #[test]
+    fn reads_fixed64_fields_as_timestamps() {
+        // 2024-01-01T00:00:00Z
+        let expected = DateTime::from_timestamp(1_704_067_200, 0).unwrap();
+
+        assert_eq!(fixed64_field(1_704_067_200).as_timestamp(), Some(expected));
+        assert_eq!(
+            fixed64_field(1_704_067_200_000).as_timestamp(),
+            Some(expected)
+        );
+        assert_eq!(
+            fixed64_field(1_704_067_200)
+                .as_timestamp()
+                .unwrap()
+                .to_rfc3339(),
+            "2024-01-01T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn other_fields_are_not_timestamps() {
+        let varint = Decoder::new(VARINT_FIELD.to_vec()).decode().unwrap();
+        assert_eq!(varint.fields[0].as_timestamp(), None);
+
+        let len = Decoder::new(len_field(&1_704_067_200_i64.to_le_bytes()))
+            .decode()
+            .unwrap();
+        assert_eq!(len.fields[0].as_timestamp(), None);
+
+        assert_eq!(fixed64_field(i64::MAX).as_timestamp(), None);
+    }
+
+    /// Returns a varint field with the field number `number`, which may be out of range.
+    fn field_numbered(number: u128) -> Vec<u8> {
+        let mut data = Vec::new();
+        write_varint(&mut data, number << 3);
+        data.push(0x01);
+        data
+    }
+
+    #[test]
+    fn rejects_out_of_range_field_numbers() {
+        for number in [0, MAX_FIELD_NUMBER as u128 + 1, u64::MAX as u128] {
+            assert!(
+                matches!(
+                    Decoder::new(field_numbered(number)).decode(),
+                    Err(DecodeError::InvalidFieldNumber(n)) if n == number
+                ),
+                "{number}"
+            );
+        }
+
+        let result = Decoder::new(field_numbered(MAX_FIELD_NUMBER as u128))
+            .decode()
+            .unwrap();
+        assert_eq!(result.fields[0].field, MAX_FIELD_NUMBER);
+    }
+
+    #[test]
+    fn best_effort_skips_a_corrupt_field_between_valid_ones() {
+        let mut data = len_field(b"before");
+        let corrupt_start = data.len();
+        // Field 2 with the invalid wire type 7
+        data.push(0x17);
+        data.extend_from_slice(&VARINT_FIELD);
+
+        let (result, errors) = Decoder::new(data.clone()).decode_best_effort();
+
+        assert!(Decoder::new(data).decode().is_err());
+        assert_eq!(
+            result.fields.iter().map(|f| f.field).collect::<Vec<_>>(),
+            [2, 1]
+        );
+        assert!(matches!(&result.fields[0].value, DecodedValue::Buffer(buf) if buf == b"before"));
+        assert!(matches!(result.fields[1].value, DecodedValue::UInt(1)));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field_start, corrupt_start);
+        assert_eq!(errors[0].field_end, corrupt_start + 1);
+        assert!(!errors[0].reason.is_empty());
+    }
+
+    #[test]
+    fn best_effort_gives_up_resyncing_after_a_bounded_distance() {
+        let mut data = VARINT_FIELD.to_vec();
+        // Field 1 with the invalid wire type 7, repeated so that no offset looks like a field
+        data.extend(std::iter::repeat_n(0x0F, MAX_RESYNC_DISTANCE + 10));
+        data.extend_from_slice(&VARINT_FIELD);
+
+        let (result, errors) = Decoder::new(data.clone()).decode_best_effort();
+
+        assert_eq!(result.fields.len(), 1);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field_start, 2);
+        assert_eq!(errors[0].field_end, 3 + MAX_RESYNC_DISTANCE);
+        assert_eq!(result.unprocessed, data[3 + MAX_RESYNC_DISTANCE..]);
+    }
+
+    #[test]
+    fn best_effort_matches_decode_on_valid_data() {
+        let mut data = VARINT_FIELD.to_vec();
+        data.extend_from_slice(&len_field(b"valid"));
+
+        let (result, errors) = Decoder::new(data.clone()).decode_best_effort();
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            result.fields.len(),
+            Decoder::new(data).decode().unwrap().fields.len()
+        );
+    }
+}
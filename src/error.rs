@@ -0,0 +1,106 @@
+use crate::decode::DecodeError;
+
+/// Errors that can occur while fetching a hotfix.
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Failed to read {}: {source}", path.display())]
+    File {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Server answered {status} with a {kind} body:\n{body}")]
+    Status {
+        status: reqwest::StatusCode,
+        kind: &'static str,
+        body: String,
+    },
+    #[error("Invalid CA certificate: {0}")]
+    Certificate(reqwest::Error),
+    #[error("Invalid base64 in {stage} response ({len} bytes): {reason}\n{preview}")]
+    Base64 {
+        stage: &'static str,
+        len: usize,
+        reason: String,
+        preview: String,
+    },
+    #[error("Invalid hex input: {0}")]
+    Hex(String),
+    #[error("{stage} returned {kind} instead of base64:\n{body}")]
+    UnexpectedBody {
+        stage: &'static str,
+        kind: &'static str,
+        body: String,
+    },
+    #[error("Failed to decode dispatch ({len} bytes): {source}\n{preview}")]
+    Dispatch {
+        len: usize,
+        preview: String,
+        source: prost::DecodeError,
+    },
+    #[error("dispatch rejected the query with retcode {retcode}: {msg} {stop_desc}")]
+    DispatchRejected {
+        retcode: u32,
+        msg: String,
+        stop_desc: String,
+    },
+    #[error("Failed to decode gateserver: {0}")]
+    Gateserver(#[from] DecodeError),
+    #[error("BinaryVersion doesn't hold a dotted game version")]
+    MissingGameVersion,
+    #[error("dispatch_seed is missing from BinaryVersion")]
+    MissingDispatchSeed,
+    #[error("gateway returned empty response")]
+    EmptyGatewayResponse,
+    #[error("no dispatch URL to query or no attempts left")]
+    AttemptsExhausted,
+    #[error("operation timed out")]
+    Timeout,
+    #[error("inferred proto is invalid: {0}")]
+    InvalidProto(String),
+    #[error("missing required fields: {0}")]
+    MissingFields(String),
+}
+
+impl AppError {
+    /// Returns whether the error may go away by trying again: timeouts, failed connections and 5xx responses.
+    /// Client errors and invalid responses are not transient.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            AppError::Http(e) => e.is_timeout() || e.is_connect(),
+            AppError::Status { status, .. } => status.is_server_error(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(code: u16) -> AppError {
+        AppError::Status {
+            status: reqwest::StatusCode::from_u16(code).unwrap(),
+            kind: "text",
+            body: String::new(),
+        }
+    }
+
+    #[test]
+    fn only_server_errors_are_transient() {
+        assert!(status(500).is_transient());
+        assert!(status(503).is_transient());
+        assert!(!status(404).is_transient());
+        assert!(!status(429).is_transient());
+        assert!(!AppError::EmptyGatewayResponse.is_transient());
+        assert!(
+            !AppError::UnexpectedBody {
+                stage: "dispatch",
+                kind: "an HTML page",
+                body: String::new(),
+            }
+            .is_transient()
+        );
+    }
+}
@@ -0,0 +1,1409 @@
+use crate::{
+    base64,
+    decode::{DecodedValue, Decoder, DecodingResult},
+    error::AppError,
+    hotfix::{AssetBundleVersion, Hotfix, InferredField, render_proto},
+    metadata::{Metadata, detect_region_tag},
+    params::DispatchParams,
+    proto::Dispatch,
+    resource::{ResourceKind, UrlPatterns, parse_resource_version},
+    util::{byte_preview, is_base64_text},
+};
+use futures_core::Stream;
+use prost::Message;
+use reqwest::{
+    Certificate, Client,
+    header::{HeaderMap, HeaderName, HeaderValue},
+};
+use std::{
+    fs,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    sync::{Semaphore, mpsc},
+    task::JoinSet,
+};
+
+/// The result of a complete dispatch and gateway query.
+#[derive(Debug)]
+pub struct FetchOutput {
+    /// Hotfix data extracted from the gateserver response.
+    pub hotfix: Hotfix,
+    /// Inferred proto definition of the gateserver message.
+    pub proto: String,
+    /// Gateserver fields whose meaning was inferred.
+    pub fields: Vec<InferredField>,
+    /// Describes which dispatch and region the hotfix was fetched from.
+    pub metadata: Metadata,
+    /// Raw decoding result of the gateserver response.
+    pub gateserver: DecodingResult,
+    /// The decoded dispatch response, listing every region. `None` when only a gateway response was decoded.
+    pub dispatch: Option<Dispatch>,
+    /// The asset bundle version found in its URL and in the gateserver fields.
+    pub asset_bundle_version: AssetBundleVersion,
+}
+
+impl FetchOutput {
+    /// One-line overview of what was extracted: the number of URLs found, the build of each resource,
+    /// and the gateserver address. Unknown values are shown as `-`.
+    pub fn summary(&self) -> String {
+        let urls_found = ResourceKind::ALL.len() - self.hotfix.missing_urls().len();
+        let versions = ResourceKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let version =
+                    parse_resource_version(self.hotfix.url(kind), &UrlPatterns::default())
+                        .map_or_else(|| String::from("-"), |version| version.to_string());
+                format!("{} {version}", kind.field_name().trim_end_matches("_url"))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let ip = match self.inferred_value("ip") {
+            Some(DecodedValue::Buffer(buf)) => String::from_utf8_lossy(buf).to_string(),
+            _ => String::from("-"),
+        };
+        let port = self
+            .hotfix
+            .ports
+            .first()
+            .map_or_else(|| String::from("-"), |port| port.to_string());
+
+        format!(
+            "{urls_found}/{} URLs, {versions}, ip {ip}, port {port}",
+            ResourceKind::ALL.len()
+        )
+    }
+
+    /// Returns the value of the top-level gateserver field inferred as `name`.
+    fn inferred_value(&self, name: &str) -> Option<&DecodedValue> {
+        let number = self.fields.iter().find(|field| field.name == name)?.number;
+        self.gateserver
+            .fields
+            .iter()
+            .find(|field| field.field == number)
+            .map(|field| &field.value)
+    }
+}
+
+/// Settings of the HTTP client used for the dispatch and gateway queries.
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    /// PEM encoded root certificate to trust in addition to the system ones.
+    pub ca_cert: Option<Vec<u8>>,
+    /// Disables TLS certificate verification entirely.
+    pub insecure: bool,
+    /// Extra headers sent with every dispatch and gateway request.
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+/// Builds the HTTP client used for the dispatch and gateway queries.
+/// Responses are transparently decompressed when served with gzip or brotli content encoding.
+pub fn build_client(options: &ClientOptions) -> Result<Client, AppError> {
+    let mut builder = Client::builder().gzip(true).brotli(true);
+
+    if let Some(pem) = &options.ca_cert {
+        let cert = Certificate::from_pem(pem).map_err(AppError::Certificate)?;
+        builder = builder.add_root_certificate(cert);
+    }
+
+    if options.insecure {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    if !options.headers.is_empty() {
+        builder = builder.default_headers(options.headers.iter().cloned().collect::<HeaderMap>());
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Steps of a hotfix fetch, as reported by `fetch_hotfix_stream`.
+#[derive(Debug)]
+pub enum ProgressEvent {
+    /// The dispatch is being queried at `url`.
+    FetchingDispatch { url: String },
+    /// The dispatch answered with `regions` regions.
+    DispatchDone { regions: usize },
+    /// The gateway of `region_name` is being queried at `url`.
+    FetchingGateway { region_name: String, url: String },
+    /// The gateway answered and its response is being decoded.
+    Decoding,
+    /// The gateserver message was decoded and `fields` of its fields were recognized.
+    Extracted { fields: usize },
+    /// The fetch succeeded.
+    Done(Box<FetchOutput>),
+    /// The dispatch didn't list any region.
+    NoRegion,
+    /// The fetch failed.
+    Failed(AppError),
+}
+
+/// Queries the global dispatch, then the gateway of the first listed region, and extracts the hotfix from its response.
+/// Returns `None` if the dispatch doesn't list any region, and an error if `dispatch_seed` is empty
+/// or if the dispatch rejected the query.
+pub async fn fetch_hotfix(
+    client: &Client,
+    dispatch_url: &str,
+    dispatch_seed: &str,
+    params: &DispatchParams,
+) -> Result<Option<FetchOutput>, AppError> {
+    fetch_hotfix_reporting(client, dispatch_url, dispatch_seed, params, &mut |_| {}).await
+}
+
+/// Limits on how often `fetch_hotfix_with_fallback` queries the servers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries of a dispatch URL after a transient error, before falling back to the next URL.
+    pub retries_per_url: u32,
+    /// Total number of `fetch_hotfix` attempts across all dispatch URLs, including retries.
+    pub max_attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries_per_url: 0,
+            max_attempts: u32::MAX,
+        }
+    }
+}
+
+/// Runs `fetch_hotfix` against each of `dispatch_urls` in turn until one succeeds.
+/// Transient errors are retried on the same URL with an increasing delay, other errors move on to the next URL.
+/// Gives up with the last error once every URL failed or `policy.max_attempts` attempts were made.
+/// A missing `dispatch_seed` fails right away.
+/// Each intermediate step of every attempt is passed to `report`.
+pub async fn fetch_hotfix_with_fallback(
+    client: &Client,
+    dispatch_urls: &[String],
+    dispatch_seed: &str,
+    params: &DispatchParams,
+    policy: RetryPolicy,
+    report: &mut (impl FnMut(ProgressEvent) + Send),
+) -> Result<Option<FetchOutput>, AppError> {
+    let mut attempts = 0;
+    let mut last_error = None;
+
+    'urls: for dispatch_url in dispatch_urls {
+        let mut retries = 0;
+        while attempts < policy.max_attempts {
+            attempts += 1;
+            match fetch_hotfix_reporting(client, dispatch_url, dispatch_seed, params, report).await
+            {
+                Err(AppError::MissingDispatchSeed) => return Err(AppError::MissingDispatchSeed),
+                Err(e) if e.is_transient() && retries < policy.retries_per_url => {
+                    retries += 1;
+                    log::warn!("{e}, retrying ({retries}/{})", policy.retries_per_url);
+                    last_error = Some(e);
+                    tokio::time::sleep(Duration::from_secs(retries.into())).await;
+                }
+                Err(e) => {
+                    log::warn!("{dispatch_url} failed: {e}");
+                    last_error = Some(e);
+                    continue 'urls;
+                }
+                result => return result,
+            }
+        }
+        break;
+    }
+
+    Err(last_error.unwrap_or(AppError::AttemptsExhausted))
+}
+
+/// Stream of the `ProgressEvent`s of a fetch running in the background, see `fetch_hotfix_stream`.
+#[derive(Debug)]
+pub struct ProgressStream(mpsc::UnboundedReceiver<ProgressEvent>);
+
+impl Stream for ProgressStream {
+    type Item = ProgressEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ProgressEvent>> {
+        self.0.poll_recv(cx)
+    }
+}
+
+/// Runs `fetch_hotfix` in a background task, yielding a `ProgressEvent` for each step as it happens.
+/// The last event is always `Done`, `NoRegion` or `Failed`, after which the stream ends.
+/// Must be called from within a Tokio runtime.
+pub fn fetch_hotfix_stream(
+    client: Client,
+    dispatch_url: String,
+    dispatch_seed: String,
+    params: DispatchParams,
+) -> ProgressStream {
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        // Sending only fails once the receiver is dropped, in which case nobody is listening anymore
+        let mut report = |event| {
+            let _ = sender.send(event);
+        };
+        let result =
+            fetch_hotfix_reporting(&client, &dispatch_url, &dispatch_seed, &params, &mut report)
+                .await;
+        report(match result {
+            Ok(Some(output)) => ProgressEvent::Done(Box::new(output)),
+            Ok(None) => ProgressEvent::NoRegion,
+            Err(err) => ProgressEvent::Failed(err),
+        });
+    });
+
+    ProgressStream(receiver)
+}
+
+/// Implementation of `fetch_hotfix`, passing each intermediate step to `report`.
+async fn fetch_hotfix_reporting(
+    client: &Client,
+    dispatch_url: &str,
+    dispatch_seed: &str,
+    params: &DispatchParams,
+    report: &mut (impl FnMut(ProgressEvent) + Send),
+) -> Result<Option<FetchOutput>, AppError> {
+    if dispatch_seed.trim().is_empty() {
+        return Err(AppError::MissingDispatchSeed);
+    }
+
+    let query_dispatch_url = build_dispatch_url(dispatch_url, params);
+
+    log::debug!("Dispatch URL: {query_dispatch_url}");
+    report(ProgressEvent::FetchingDispatch {
+        url: query_dispatch_url.clone(),
+    });
+
+    let query_dispatch_response =
+        get_text(client, &query_dispatch_url, params.dispatch_timeout).await?;
+
+    let dispatch_decoded_base64 =
+        decode_base64("dispatch", &query_dispatch_response, &params.envelope_field)?;
+
+    let dispatch_decoded_message =
+        Dispatch::decode(&*dispatch_decoded_base64).map_err(|source| AppError::Dispatch {
+            len: dispatch_decoded_base64.len(),
+            preview: byte_preview(&dispatch_decoded_base64, 32),
+            source,
+        })?;
+    report(ProgressEvent::DispatchDone {
+        regions: dispatch_decoded_message.region_list.len(),
+    });
+
+    if dispatch_decoded_message.region_list.is_empty() {
+        // A non-zero retcode tells why the dispatch didn't list any region, e.g. an unsupported version
+        if dispatch_decoded_message.retcode != 0 {
+            return Err(AppError::DispatchRejected {
+                retcode: dispatch_decoded_message.retcode,
+                msg: dispatch_decoded_message.msg,
+                stop_desc: dispatch_decoded_message.stop_desc,
+            });
+        }
+        log::debug!("region_list is empty");
+        return Ok(None);
+    }
+
+    let region = &dispatch_decoded_message.region_list[0];
+    let metadata = Metadata {
+        region_tag: detect_region_tag(&query_dispatch_url),
+        dispatch_url: query_dispatch_url,
+        region_name: region.name.clone(),
+        dispatch_seed: dispatch_seed.to_string(),
+    };
+
+    let query_gateway_url = build_gateway_url(
+        &region.dispatch_url,
+        &params.for_region(&region.name),
+        dispatch_seed,
+    );
+
+    log::debug!("Gateway URL: {query_gateway_url}");
+    report(ProgressEvent::FetchingGateway {
+        region_name: region.name.clone(),
+        url: query_gateway_url.clone(),
+    });
+
+    let query_gateway_response =
+        get_text(client, &query_gateway_url, params.gateway_timeout).await?;
+
+    report(ProgressEvent::Decoding);
+    let mut output =
+        decode_gateway_response(&query_gateway_response, metadata, &params.envelope_field)?;
+    output.dispatch = Some(dispatch_decoded_message);
+    report(ProgressEvent::Extracted {
+        fields: output.fields.len(),
+    });
+
+    Ok(Some(output))
+}
+
+/// Returns the full URL of the global dispatch query sent to `base`.
+pub fn build_dispatch_url(base: &str, params: &DispatchParams) -> String {
+    format!("{}?{}", base, params.dispatch_query())
+}
+
+/// Returns the full URL of the region gateway query sent to `base`.
+/// Region channel overrides are not applied, pass `params.for_region(..)` for them.
+pub fn build_gateway_url(base: &str, params: &DispatchParams, dispatch_seed: &str) -> String {
+    format!("{}?{}", base, params.gateway_query(dispatch_seed))
+}
+
+/// Largest body size reserved upfront from an announced content length, so a bogus length can't exhaust memory.
+const MAX_PREALLOCATED_BODY: u64 = 16 * 1024 * 1024;
+
+/// Returns the body of `url`, failing if the request takes longer than `timeout` or if the response status is
+/// not a success, in which case the body is kept in the error.
+/// The body is read in chunks, logging the download progress at debug level.
+/// `file://` URLs are read from disk instead, ignoring their query string.
+async fn get_text(
+    client: &Client,
+    url: &str,
+    timeout: Option<Duration>,
+) -> Result<String, AppError> {
+    if let Some(path) = reqwest::Url::parse(url)
+        .ok()
+        .filter(|url| url.scheme() == "file")
+        .and_then(|url| url.to_file_path().ok())
+    {
+        return fs::read_to_string(&path).map_err(|source| AppError::File { path, source });
+    }
+
+    let mut request = client.get(url);
+    if let Some(timeout) = timeout {
+        request = request.timeout(timeout);
+    }
+
+    let mut response = request.send().await?;
+    let status = response.status();
+    let total = response.content_length();
+    let mut body = Vec::with_capacity(total.unwrap_or(0).min(MAX_PREALLOCATED_BODY) as usize);
+
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        match total {
+            Some(total) => log::debug!("Downloaded {}/{total} bytes from {url}", body.len()),
+            None => log::debug!("Downloaded {} bytes from {url}", body.len()),
+        }
+    }
+
+    let body = String::from_utf8_lossy(&body).into_owned();
+    if !status.is_success() {
+        let body = body.trim();
+        return Err(AppError::Status {
+            status,
+            kind: body_kind(body).unwrap_or("text"),
+            body: body.chars().take(512).collect(),
+        });
+    }
+
+    Ok(body)
+}
+
+/// Decodes a base64 gateserver response, as returned by the gateway, and extracts the hotfix from it.
+/// A response wrapped in a JSON object is decoded from its `envelope_field` string field.
+pub fn decode_gateway_response(
+    response: &str,
+    metadata: Metadata,
+    envelope_field: &str,
+) -> Result<FetchOutput, AppError> {
+    let mut gateserver_decoded_base64 = decode_base64("gateway", response, envelope_field)?;
+
+    // Some gateways encode the message twice. Only one more pass is made, and only if the bytes
+    // are base64 text that doesn't decode as a message.
+    if is_base64_text(&gateserver_decoded_base64)
+        && Decoder::new(gateserver_decoded_base64.clone())
+            .decode()
+            .is_err()
+        && let Ok(inner) = base64::decode(&String::from_utf8_lossy(&gateserver_decoded_base64))
+    {
+        log::info!("Gateway response is base64 encoded twice");
+        gateserver_decoded_base64 = inner;
+    }
+
+    decode_gateserver(gateserver_decoded_base64, metadata)
+}
+
+/// Decodes a raw gateserver message and extracts the hotfix from it.
+/// Fails with `AppError::EmptyGatewayResponse` if the message is empty, e.g. for a `====` or `{"content":""}` response.
+pub fn decode_gateserver(message: Vec<u8>, metadata: Metadata) -> Result<FetchOutput, AppError> {
+    if message.is_empty() {
+        return Err(AppError::EmptyGatewayResponse);
+    }
+
+    let mut decoder = Decoder::new(message);
+
+    let gateserver_decoded_message = decoder.decode()?;
+
+    let patterns = UrlPatterns::default();
+    let (hotfix, fields) = Hotfix::infer_fields(
+        &gateserver_decoded_message,
+        &metadata.region_name,
+        &patterns,
+    );
+    let proto = render_proto(&fields);
+
+    let asset_bundle_version =
+        AssetBundleVersion::reconcile(&hotfix, &gateserver_decoded_message, &fields, &patterns);
+    if let AssetBundleVersion {
+        url_version: Some(url_version),
+        mdk_res_version: Some(mdk_res_version),
+        consistent: false,
+    } = asset_bundle_version
+    {
+        log::warn!(
+            "asset_bundle_url is for build {url_version}, but mdk_res_version is {mdk_res_version}"
+        );
+    }
+
+    Ok(FetchOutput {
+        hotfix,
+        proto,
+        fields,
+        metadata,
+        gateserver: gateserver_decoded_message,
+        dispatch: None,
+        asset_bundle_version,
+    })
+}
+
+/// Decodes a base64 response body, describing the body in the error if it isn't valid base64.
+/// A JSON object body is treated as an envelope holding the base64 in its `envelope_field` string field.
+/// HTML and other JSON bodies, as returned by error and rate-limit pages, are reported with their text instead.
+fn decode_base64(
+    stage: &'static str,
+    body: &str,
+    envelope_field: &str,
+) -> Result<Vec<u8>, AppError> {
+    let mut body = body.trim();
+
+    let envelope = body
+        .starts_with('{')
+        .then(|| serde_json::from_str::<serde_json::Value>(body).ok())
+        .flatten();
+    let content = envelope
+        .as_ref()
+        .and_then(|envelope| envelope.get(envelope_field)?.as_str());
+    if let Some(content) = content {
+        body = content.trim();
+    } else if let Some(kind) = body_kind(body) {
+        return Err(AppError::UnexpectedBody {
+            stage,
+            kind,
+            body: body.chars().take(512).collect(),
+        });
+    }
+
+    base64::decode(body).map_err(|e| AppError::Base64 {
+        stage,
+        len: body.len(),
+        reason: e.to_string(),
+        preview: byte_preview(body.as_bytes(), 32),
+    })
+}
+
+/// Tells HTML and JSON bodies apart from base64 ones by their first character, as error and rate-limit pages are
+/// served in either format.
+fn body_kind(body: &str) -> Option<&'static str> {
+    match body.chars().next() {
+        Some('<') => Some("HTML"),
+        Some('{') | Some('[') => Some("JSON"),
+        _ => None,
+    }
+}
+
+/// Default number of HEAD requests `verify_urls` sends at once.
+pub const DEFAULT_VERIFY_CONCURRENCY: usize = 4;
+
+/// The outcome of checking a resource URL with a HEAD request, see `verify_urls`.
+#[derive(Debug)]
+pub struct UrlCheck {
+    /// Resource the URL points to.
+    pub kind: ResourceKind,
+    /// The checked URL.
+    pub url: String,
+    /// Status of the response, or the error the request failed with.
+    pub result: Result<reqwest::StatusCode, AppError>,
+}
+
+impl UrlCheck {
+    /// Returns whether the server answered with a success or redirect status.
+    pub fn is_reachable(&self) -> bool {
+        self.result
+            .as_ref()
+            .is_ok_and(|status| status.is_success() || status.is_redirection())
+    }
+}
+
+/// Sends a HEAD request to every resource URL of `hotfix`, with at most `concurrency` requests in flight
+/// so that the CDN doesn't rate-limit the checks. Missing URLs are skipped.
+/// The checks are returned in the order of `ResourceKind::ALL`.
+pub async fn verify_urls(client: &Client, hotfix: &Hotfix, concurrency: usize) -> Vec<UrlCheck> {
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+
+    for kind in ResourceKind::ALL {
+        let url = hotfix.url(kind).to_string();
+        if url.is_empty() {
+            continue;
+        }
+
+        let client = client.clone();
+        let semaphore = Arc::clone(&semaphore);
+        tasks.spawn(async move {
+            let _permit = semaphore
+                .acquire_owned()
+                .await
+                .expect("the semaphore is never closed");
+            let result = client
+                .head(&url)
+                .send()
+                .await
+                .map(|response| response.status())
+                .map_err(AppError::from);
+            UrlCheck { kind, url, result }
+        });
+    }
+
+    let mut checks = tasks.join_all().await;
+    checks.sort_by_key(|check| {
+        ResourceKind::ALL
+            .iter()
+            .position(|&kind| kind == check.kind)
+    });
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::{ChannelOverride, DEFAULT_ENVELOPE_FIELD};
+    use crate::test_util::{
+        DISPATCH_SEED, LUA_URL, MockResponse, MockServer, REGION_NAME, capture_logs,
+        dispatch_and_gateway, dispatch_response, gateserver_message, gateway_response,
+        push_len_field,
+    };
+    use std::sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    #[tokio::test]
+    async fn metadata_reflects_the_region_of_the_dispatch() {
+        let server = dispatch_and_gateway(|_| MockResponse::ok(gateway_response()));
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let output = fetch_hotfix(
+            &client,
+            &server.url("/dispatch"),
+            DISPATCH_SEED,
+            &DispatchParams::new("2.2.0"),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(output.metadata.region_name, REGION_NAME);
+        assert_eq!(output.metadata.dispatch_seed, DISPATCH_SEED);
+        assert!(
+            output
+                .metadata
+                .dispatch_url
+                .starts_with(&server.url("/dispatch?version=2.2.0&"))
+        );
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].path.starts_with("/gateway?"));
+    }
+
+    #[tokio::test]
+    async fn empty_dispatch_seed_fails_before_querying() {
+        let server = dispatch_and_gateway(|_| MockResponse::ok(gateway_response()));
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        for seed in ["", "  "] {
+            let result = fetch_hotfix(
+                &client,
+                &server.url("/dispatch"),
+                seed,
+                &DispatchParams::new("2.2.0"),
+            )
+            .await;
+
+            assert!(matches!(result, Err(AppError::MissingDispatchSeed)));
+        }
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn empty_gateway_response_fails_clearly() {
+        let server = dispatch_and_gateway(|_| MockResponse::ok(""));
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let result = fetch_hotfix(
+            &client,
+            &server.url("/dispatch"),
+            DISPATCH_SEED,
+            &DispatchParams::new("2.2.0"),
+        )
+        .await;
+
+        assert!(matches!(result, Err(AppError::EmptyGatewayResponse)));
+    }
+
+    #[test]
+    fn responses_decoding_to_nothing_are_empty() {
+        for response in ["  ", "====", r#"{"content":""}"#] {
+            let result = decode_gateway_response(response, Metadata::default(), "content");
+
+            assert!(
+                matches!(result, Err(AppError::EmptyGatewayResponse)),
+                "{response}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn decodes_gzip_encoded_responses() {
+        use flate2::{Compression, write::GzEncoder};
+        use std::io::Write as _;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(gateway_response().as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let server = dispatch_and_gateway(move |_| {
+            MockResponse::ok(gzipped.clone()).with_header("Content-Encoding", "gzip")
+        });
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let output = fetch_hotfix(
+            &client,
+            &server.url("/dispatch"),
+            DISPATCH_SEED,
+            &DispatchParams::new("2.2.0"),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(output.hotfix.lua_url, LUA_URL);
+        let accept_encoding = server.requests()[1]
+            .header("accept-encoding")
+            .unwrap_or_default()
+            .to_string();
+        assert!(accept_encoding.contains("gzip"));
+        assert!(accept_encoding.contains("br"));
+    }
+
+    #[tokio::test]
+    async fn gateway_query_uses_the_channels_of_the_region() {
+        let server = dispatch_and_gateway(|_| MockResponse::ok(gateway_response()));
+        let client = build_client(&ClientOptions::default()).unwrap();
+        let params = DispatchParams::new("2.2.0").with_region_channel(
+            REGION_NAME,
+            ChannelOverride {
+                channel_id: Some(5),
+                sub_channel_id: Some(6),
+            },
+        );
+
+        fetch_hotfix(&client, &server.url("/dispatch"), DISPATCH_SEED, &params)
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        assert!(requests[0].path.contains("&channel_id=1&sub_channel_id=1&"));
+        assert!(requests[1].path.contains("&channel_id=5&sub_channel_id=6&"));
+    }
+
+    #[tokio::test]
+    async fn reports_html_pages_served_instead_of_base64() {
+        let page = "<html><body>Too many requests, slow down</body></html>";
+        let server = MockServer::start(move |_, _| MockResponse::ok(page));
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let result = fetch_hotfix(
+            &client,
+            &server.url("/dispatch"),
+            DISPATCH_SEED,
+            &DispatchParams::new("2.2.0"),
+        )
+        .await;
+
+        let Err(err @ AppError::UnexpectedBody { stage, kind, body }) = &result else {
+            panic!("expected an unexpected body error, got {result:?}");
+        };
+        assert_eq!((*stage, *kind, body.as_str()), ("dispatch", "HTML", page));
+        assert!(
+            err.to_string()
+                .starts_with("dispatch returned HTML instead of base64")
+        );
+    }
+
+    #[test]
+    fn detects_html_and_json_error_bodies() {
+        for (body, expected_kind) in [
+            (
+                "  <!DOCTYPE html><html>503 Service Unavailable</html>",
+                "HTML",
+            ),
+            (r#"{"retcode":-1,"message":"rate limited"}"#, "JSON"),
+            (r#"["error"]"#, "JSON"),
+        ] {
+            let result = decode_base64("gateway", body, "content");
+
+            assert!(
+                matches!(
+                    result,
+                    Err(AppError::UnexpectedBody { stage: "gateway", kind, .. }) if kind == expected_kind
+                ),
+                "{body}"
+            );
+        }
+    }
+
+    /// Self-signed CA certificate, valid until 2126.
+    const CA_CERT: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBlDCCATugAwIBAgIUT2u8HqXNDgkeVpOgXL+di3x2Pc8wCgYIKoZIzj0EAwIw
+HzEdMBsGA1UEAwwUZmV0Y2gtaG90Zml4IHRlc3QgQ0EwIBcNMjYxMDE2MDMwMDUy
+WhgPMjEyNjA5MjIwMzAwNTJaMB8xHTAbBgNVBAMMFGZldGNoLWhvdGZpeCB0ZXN0
+IENBMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAESpLDu0RpxVVPi1Sj2kjw3qls
+WbKMUrSyC6+XzfEBOUUVBS1ly+LjIhDjH3or2Rjr/L3HCKPiJAKXbUCwPlEmOaNT
+MFEwHQYDVR0OBBYEFB4yCNA9djEoUkbDsA6K0UDAjbOwMB8GA1UdIwQYMBaAFB4y
+CNA9djEoUkbDsA6K0UDAjbOwMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwID
+RwAwRAIgXN0DFvTZweu6VcZwg+0bYPQ3L/rm01H/XPvLZ/fTOGQCIGVtakMJD5/Z
+uf8DOeaG/QOZieujIMB8nWXyVR6D2LXd
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn builds_clients_with_custom_tls_settings() {
+        let options = ClientOptions {
+            ca_cert: Some(CA_CERT.as_bytes().to_vec()),
+            insecure: true,
+            ..Default::default()
+        };
+        assert!(build_client(&options).is_ok());
+
+        let options = ClientOptions {
+            ca_cert: Some(b"not a certificate".to_vec()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            build_client(&options),
+            Err(AppError::Certificate(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn reads_file_dispatch_urls_from_disk() {
+        let server = MockServer::start(|_, _| MockResponse::ok(gateway_response()));
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dispatch.b64");
+        fs::write(&path, dispatch_response(&server.url("/gateway"))).unwrap();
+        let dispatch_url = reqwest::Url::from_file_path(&path).unwrap();
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let output = fetch_hotfix(
+            &client,
+            dispatch_url.as_str(),
+            DISPATCH_SEED,
+            &DispatchParams::new("2.2.0"),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(output.metadata.region_name, REGION_NAME);
+        assert_eq!(output.hotfix.lua_url, LUA_URL);
+        // Only the gateway is queried over HTTP
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].path.starts_with("/gateway?"));
+    }
+
+    #[tokio::test]
+    async fn missing_file_urls_fail_with_their_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing.b64");
+        let url = reqwest::Url::from_file_path(&path).unwrap();
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let result = get_text(&client, &format!("{url}?version=2.2.0"), None).await;
+
+        assert!(matches!(result, Err(AppError::File { path: failed, .. }) if failed == path));
+    }
+
+    #[test]
+    fn summary_reflects_the_extracted_hotfix() {
+        let mut output = decode_gateserver(gateserver_message(), Metadata::default()).unwrap();
+
+        assert_eq!(
+            output.summary(),
+            "4/4 URLs, asset_bundle 7576543, ex_resource 7581234, lua 7576543, ifix 7570000, \
+             ip 10.0.0.1, port 23301"
+        );
+
+        output.hotfix.ifix_url.clear();
+        output.hotfix.ports.clear();
+        assert_eq!(
+            output.summary(),
+            "3/4 URLs, asset_bundle 7576543, ex_resource 7581234, lua 7576543, ifix -, \
+             ip 10.0.0.1, port -"
+        );
+    }
+
+    /// Collects every event of `stream` until it ends.
+    async fn collect_events(mut stream: ProgressStream) -> Vec<ProgressEvent> {
+        let mut events = Vec::new();
+        while let Some(event) = std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await
+        {
+            events.push(event);
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn stream_reports_each_step_in_order() {
+        let server = dispatch_and_gateway(|_| MockResponse::ok(gateway_response()));
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let events = collect_events(fetch_hotfix_stream(
+            client,
+            server.url("/dispatch"),
+            DISPATCH_SEED.to_string(),
+            DispatchParams::new("2.2.0"),
+        ))
+        .await;
+
+        let [
+            ProgressEvent::FetchingDispatch { url: dispatch_url },
+            ProgressEvent::DispatchDone { regions: 1 },
+            ProgressEvent::FetchingGateway {
+                region_name,
+                url: gateway_url,
+            },
+            ProgressEvent::Decoding,
+            ProgressEvent::Extracted { fields },
+            ProgressEvent::Done(output),
+        ] = events.as_slice()
+        else {
+            panic!("unexpected events: {events:?}");
+        };
+        assert!(dispatch_url.starts_with(&server.url("/dispatch?")));
+        assert_eq!(region_name, REGION_NAME);
+        assert!(gateway_url.starts_with(&server.url("/gateway?")));
+        assert_eq!(*fields, output.fields.len());
+        assert_eq!(output.hotfix.lua_url, LUA_URL);
+    }
+
+    #[tokio::test]
+    async fn stream_ends_with_the_failure() {
+        let server = dispatch_and_gateway(|_| MockResponse::status(500, "oops"));
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let events = collect_events(fetch_hotfix_stream(
+            client,
+            server.url("/dispatch"),
+            DISPATCH_SEED.to_string(),
+            DispatchParams::new("2.2.0"),
+        ))
+        .await;
+
+        assert_eq!(events.len(), 4);
+        assert!(matches!(
+            events.last(),
+            Some(ProgressEvent::Failed(AppError::Status { .. }))
+        ));
+    }
+
+    #[test]
+    fn builds_the_dispatch_url() {
+        let params = DispatchParams::new("2.2.0").with_channel_id(5);
+
+        assert_eq!(
+            build_dispatch_url(
+                "https://globaldp-prod-cn01.bhsr.com/query_dispatch",
+                &params
+            ),
+            "https://globaldp-prod-cn01.bhsr.com/query_dispatch?\
+             version=2.2.0&language_type=3&platform_type=3&channel_id=5&sub_channel_id=1&is_new_format=1"
+        );
+    }
+
+    #[test]
+    fn builds_the_gateway_url() {
+        let params = DispatchParams::new("2.2.0").with_is_need_url(false);
+
+        assert_eq!(
+            build_gateway_url(
+                "https://prod-gf-cn-dp01.bhsr.com/query_gateway",
+                &params,
+                DISPATCH_SEED
+            ),
+            "https://prod-gf-cn-dp01.bhsr.com/query_gateway?\
+             version=2.2.0&platform_type=1&language_type=3&dispatch_seed=seed&channel_id=1&sub_channel_id=1&is_need_url=0"
+        );
+    }
+
+    const RETRY_ONCE: RetryPolicy = RetryPolicy {
+        retries_per_url: 1,
+        max_attempts: u32::MAX,
+    };
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let server = MockServer::start(|_, _| MockResponse::status(404, "Not Found"));
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let result = fetch_hotfix_with_fallback(
+            &client,
+            &[server.url("/dispatch")],
+            DISPATCH_SEED,
+            &DispatchParams::new("2.2.0"),
+            RETRY_ONCE,
+            &mut |_| {},
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(AppError::Status { status, body, .. })
+                if status == reqwest::StatusCode::NOT_FOUND && body == "Not Found"
+        ));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_retried() {
+        let server = dispatch_and_gateway(|index| match index {
+            0 => MockResponse::status(503, "Service Unavailable"),
+            _ => MockResponse::ok(gateway_response()),
+        });
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let output = fetch_hotfix_with_fallback(
+            &client,
+            &[server.url("/dispatch")],
+            DISPATCH_SEED,
+            &DispatchParams::new("2.2.0"),
+            RETRY_ONCE,
+            &mut |_| {},
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(output.hotfix.lua_url, LUA_URL);
+        // Each attempt queries the dispatch, then the gateway
+        assert_eq!(server.requests().len(), 4);
+    }
+
+    #[test]
+    fn decodes_a_pasted_gateway_response() {
+        let pasted = format!("  {}\r\n", gateway_response());
+
+        let output =
+            decode_gateway_response(&pasted, Metadata::default(), DEFAULT_ENVELOPE_FIELD).unwrap();
+
+        assert_eq!(output.hotfix.lua_url, LUA_URL);
+        assert!(output.dispatch.is_none());
+    }
+
+    #[tokio::test]
+    async fn surfaces_the_retcode_of_a_rejected_dispatch() {
+        let dispatch = Dispatch {
+            retcode: 70,
+            msg: String::from("Version not supported"),
+            stop_desc: String::from("Please update the game"),
+            ..Default::default()
+        };
+        let body = base64::encode(&dispatch.encode_to_vec());
+        let server = MockServer::start(move |_, _| MockResponse::ok(body.clone()));
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let result = fetch_hotfix(
+            &client,
+            &server.url("/dispatch"),
+            DISPATCH_SEED,
+            &DispatchParams::new("2.2.0"),
+        )
+        .await;
+
+        let Err(error @ AppError::DispatchRejected { retcode: 70, .. }) = result else {
+            panic!("expected the dispatch to be rejected");
+        };
+        assert_eq!(
+            error.to_string(),
+            "dispatch rejected the query with retcode 70: Version not supported Please update the game"
+        );
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn empty_region_list_without_retcode_is_not_an_error() {
+        let body = base64::encode(&Dispatch::default().encode_to_vec());
+        let server = MockServer::start(move |_, _| MockResponse::ok(body.clone()));
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let result = fetch_hotfix(
+            &client,
+            &server.url("/dispatch"),
+            DISPATCH_SEED,
+            &DispatchParams::new("2.2.0"),
+        )
+        .await;
+
+        assert!(matches!(result, Ok(None)));
+    }
+
+    #[test]
+    fn decodes_json_wrapped_responses() {
+        let wrapped =
+            serde_json::json!({ "retcode": 0, "content": gateway_response() }).to_string();
+        let output =
+            decode_gateway_response(&wrapped, Metadata::default(), DEFAULT_ENVELOPE_FIELD).unwrap();
+        assert_eq!(output.hotfix.lua_url, LUA_URL);
+
+        let wrapped = serde_json::json!({ "data": gateway_response() }).to_string();
+        let output = decode_gateway_response(&wrapped, Metadata::default(), "data").unwrap();
+        assert_eq!(output.hotfix.lua_url, LUA_URL);
+
+        // Without the envelope field, the JSON is an error body
+        assert!(matches!(
+            decode_gateway_response(&wrapped, Metadata::default(), DEFAULT_ENVELOPE_FIELD),
+            Err(AppError::UnexpectedBody { kind: "JSON", .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn decodes_a_json_wrapped_dispatch() {
+        let gateway_url = Arc::new(Mutex::new(String::new()));
+        let url = Arc::clone(&gateway_url);
+        let server = MockServer::start(move |request, _| {
+            if request.path.starts_with("/dispatch") {
+                let content = dispatch_response(&url.lock().unwrap());
+                MockResponse::ok(serde_json::json!({ "content": content }).to_string())
+                    .with_header("Content-Type", "application/json")
+            } else {
+                MockResponse::ok(gateway_response())
+            }
+        });
+        *gateway_url.lock().unwrap() = server.url("/gateway");
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let output = fetch_hotfix(
+            &client,
+            &server.url("/dispatch"),
+            DISPATCH_SEED,
+            &DispatchParams::new("2.2.0"),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(output.metadata.region_name, REGION_NAME);
+        assert_eq!(output.hotfix.lua_url, LUA_URL);
+    }
+
+    #[tokio::test]
+    async fn attempts_are_bounded_across_dispatch_urls() {
+        let server = MockServer::start(|_, _| MockResponse::status(404, "Not Found"));
+        let dispatch_urls = ["/a", "/b", "/c"].map(|path| server.url(path));
+        let client = build_client(&ClientOptions::default()).unwrap();
+        let policy = RetryPolicy {
+            retries_per_url: 3,
+            max_attempts: 2,
+        };
+
+        let result = fetch_hotfix_with_fallback(
+            &client,
+            &dispatch_urls,
+            DISPATCH_SEED,
+            &DispatchParams::new("2.2.0"),
+            policy,
+            &mut |_| {},
+        )
+        .await;
+
+        assert!(matches!(result, Err(AppError::Status { .. })));
+        let paths = server
+            .requests()
+            .into_iter()
+            .map(|request| request.path.split('?').next().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["/a", "/b"]);
+    }
+
+    #[tokio::test]
+    async fn no_attempt_left_fails_without_querying() {
+        let server = MockServer::start(|_, _| MockResponse::ok(gateway_response()));
+        let client = build_client(&ClientOptions::default()).unwrap();
+        let policy = RetryPolicy {
+            retries_per_url: 0,
+            max_attempts: 0,
+        };
+
+        let result = fetch_hotfix_with_fallback(
+            &client,
+            &[server.url("/dispatch")],
+            DISPATCH_SEED,
+            &DispatchParams::new("2.2.0"),
+            policy,
+            &mut |_| {},
+        )
+        .await;
+
+        assert!(matches!(result, Err(AppError::AttemptsExhausted)));
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn returns_the_decoded_dispatch() {
+        let server = dispatch_and_gateway(|_| MockResponse::ok(gateway_response()));
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let output = fetch_hotfix(
+            &client,
+            &server.url("/dispatch"),
+            DISPATCH_SEED,
+            &DispatchParams::new("2.2.0"),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let served = base64::decode(&dispatch_response(&server.url("/gateway"))).unwrap();
+        assert_eq!(output.dispatch, Some(Dispatch::decode(&*served).unwrap()));
+        assert_eq!(output.dispatch.unwrap().region_list[0].name, REGION_NAME);
+    }
+
+    #[test]
+    fn decodes_double_base64_responses() {
+        let twice = base64::encode(gateway_response().as_bytes());
+
+        let mut output = None;
+        let logs = capture_logs(|| {
+            output = Some(
+                decode_gateway_response(&twice, Metadata::default(), DEFAULT_ENVELOPE_FIELD)
+                    .unwrap(),
+            )
+        });
+
+        assert_eq!(output.unwrap().hotfix.lua_url, LUA_URL);
+        assert!(logs.contains(&String::from(
+            "INFO Gateway response is base64 encoded twice"
+        )));
+    }
+
+    #[test]
+    fn decodes_at_most_two_base64_passes() {
+        let thrice = base64::encode(base64::encode(gateway_response().as_bytes()).as_bytes());
+
+        let output = decode_gateway_response(&thrice, Metadata::default(), DEFAULT_ENVELOPE_FIELD);
+
+        // The second pass yields base64 text again, which is not a valid message
+        assert!(matches!(output, Err(AppError::Gateserver(_))));
+    }
+
+    const SLOW: Duration = Duration::from_millis(500);
+    const SHORT_TIMEOUT: Duration = Duration::from_millis(100);
+
+    #[tokio::test]
+    async fn gateway_timeout_applies_to_the_gateway_only() {
+        let server =
+            dispatch_and_gateway(|_| MockResponse::ok(gateway_response()).with_delay(SLOW));
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let params = DispatchParams::new("2.2.0").with_dispatch_timeout(SHORT_TIMEOUT);
+        let result = fetch_hotfix(&client, &server.url("/dispatch"), DISPATCH_SEED, &params).await;
+        assert!(result.unwrap().is_some());
+
+        let params = DispatchParams::new("2.2.0").with_gateway_timeout(SHORT_TIMEOUT);
+        let result = fetch_hotfix(&client, &server.url("/dispatch"), DISPATCH_SEED, &params).await;
+        assert!(matches!(result, Err(AppError::Http(e)) if e.is_timeout()));
+    }
+
+    #[tokio::test]
+    async fn dispatch_timeout_applies_to_the_dispatch_only() {
+        let server =
+            MockServer::start(|_, _| MockResponse::ok(gateway_response()).with_delay(SLOW));
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let params = DispatchParams::new("2.2.0")
+            .with_dispatch_timeout(SHORT_TIMEOUT)
+            .with_gateway_timeout(Duration::from_secs(30));
+        let result = fetch_hotfix(&client, &server.url("/dispatch"), DISPATCH_SEED, &params).await;
+
+        assert!(matches!(result, Err(AppError::Http(e)) if e.is_timeout()));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sends_custom_headers_with_every_request() {
+        let server = dispatch_and_gateway(|_| MockResponse::ok(gateway_response()));
+        let options = ClientOptions {
+            headers: vec![(
+                HeaderName::from_static("x-auth-token"),
+                HeaderValue::from_static("secret"),
+            )],
+            ..Default::default()
+        };
+        let client = build_client(&options).unwrap();
+
+        fetch_hotfix(
+            &client,
+            &server.url("/dispatch"),
+            DISPATCH_SEED,
+            &DispatchParams::new("2.2.0"),
+        )
+        .await
+        .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        for request in requests {
+            assert_eq!(
+                request.header("x-auth-token"),
+                Some("secret"),
+                "{}",
+                request.path
+            );
+        }
+    }
+
+    #[test]
+    fn logs_the_download_progress_of_the_body() {
+        let body = "A".repeat(256 * 1024);
+        let served = body.clone();
+        let server = MockServer::start(move |_, _| MockResponse::ok(served.clone()));
+        let url = server.url("/gateway");
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        // Logs are captured per thread, so the request runs on this one
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let mut text = None;
+        let logs = capture_logs(|| {
+            text = Some(runtime.block_on(get_text(&client, &url, None)).unwrap());
+        });
+
+        assert_eq!(text.unwrap(), body);
+        let progress = logs
+            .iter()
+            .filter(|log| log.starts_with("DEBUG Downloaded "))
+            .collect::<Vec<_>>();
+        assert!(!progress.is_empty());
+        assert_eq!(
+            progress.last().unwrap().as_str(),
+            format!("DEBUG Downloaded {0}/{0} bytes from {url}", body.len())
+        );
+    }
+
+    #[test]
+    fn warns_when_the_asset_bundle_version_disagrees() {
+        let logs = capture_logs(|| {
+            let output =
+                decode_gateway_response(&gateway_response(), Metadata::default(), "content")
+                    .unwrap();
+            assert!(output.asset_bundle_version.consistent);
+        });
+        assert!(!logs.iter().any(|log| log.starts_with("WARN")));
+
+        let mut message = Vec::new();
+        push_len_field(
+            &mut message,
+            4,
+            b"https://autopatchcn.bhsr.com/asb/V2.2Live/output_7563532_5c6f0d11b0e5/client",
+        );
+        push_len_field(&mut message, 6, LUA_URL.as_bytes());
+        push_len_field(&mut message, 8, b"7576543");
+        let logs = capture_logs(|| {
+            let output = decode_gateserver(message, Metadata::default()).unwrap();
+            assert!(!output.asset_bundle_version.consistent);
+        });
+
+        assert!(logs.contains(&String::from(
+            "WARN asset_bundle_url is for build 7563532, but mdk_res_version is 7576543"
+        )));
+    }
+
+    #[test]
+    fn decodes_a_hex_encoded_gateserver_message() {
+        let hex = gateserver_message()
+            .chunks(16)
+            .map(|line| {
+                line.iter()
+                    .map(|byte| format!("0x{byte:02x}"))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let message = crate::util::decode_hex(&hex).unwrap();
+        let output = decode_gateserver(message, Metadata::default()).unwrap();
+
+        assert_eq!(output.hotfix.lua_url, LUA_URL);
+        assert_eq!(output.hotfix.ports, [23301]);
+    }
+
+    #[test]
+    fn summary_shows_the_primary_port() {
+        let mut message = gateserver_message();
+        crate::test_util::push_varint_field(&mut message, 9, 23302);
+
+        let output = decode_gateserver(message, Metadata::default()).unwrap();
+
+        assert!(output.summary().ends_with(", port 23301"));
+    }
+    #[tokio::test]
+    async fn verify_bounds_the_concurrent_requests() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let server = {
+            let (in_flight, max_in_flight) = (Arc::clone(&in_flight), Arc::clone(&max_in_flight));
+            MockServer::start(move |request, _| {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(200));
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                if request.path.contains("/ifix/") {
+                    MockResponse::status(404, "")
+                } else {
+                    MockResponse::ok("")
+                }
+            })
+        };
+        let hotfix = Hotfix {
+            asset_bundle_url: server.url("/asb/output_1_a/client"),
+            ex_resource_url: server.url("/design_data/output_2_b/client"),
+            lua_url: server.url("/lua/output_3_c/client"),
+            ifix_url: server.url("/ifix/output_4_d/client"),
+            ..Default::default()
+        };
+        let client = build_client(&ClientOptions::default()).unwrap();
+
+        let checks = verify_urls(&client, &hotfix, 2).await;
+
+        assert_eq!(server.requests().len(), 4);
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            checks.iter().map(|check| check.kind).collect::<Vec<_>>(),
+            ResourceKind::ALL
+        );
+        assert_eq!(
+            checks
+                .iter()
+                .map(UrlCheck::is_reachable)
+                .collect::<Vec<_>>(),
+            [true, true, true, false]
+        );
+    }
+}
@@ -1,12 +1,16 @@
 use crate::{
-    decode::{DecodedValue, DecodingResult, WireType},
-    proto::Dispatch,
+    decode::{Decoded, DecodedValue, DecodingResult, WireType},
+    resource::{
+        ResourceKind, UrlPatterns, classify_url, parse_release_version, parse_resource_version,
+    },
     util::{get_ip_address, is_ec2b_base64},
 };
-use serde::Serialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
 /// A struct representing the hotfix data, containing URLs and version information.
-#[derive(Serialize, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
 pub struct Hotfix {
     /// URL for the asset bundle.
     pub asset_bundle_url: String,
@@ -20,33 +24,248 @@ pub struct Hotfix {
     pub custom_mdk_res_version: u32,
     /// Version number for the ifix resource.
     pub custom_ifix_version: u32,
+    /// Message shown instead of the resources, e.g. during maintenance.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub access_message: String,
+    /// Gateserver ports, in the order they appear in the message. The first one is the primary port.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<u16>,
+}
+
+/// A field whose value differs between two hotfixes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotfixChange {
+    /// Name of the field, as written in the JSON output.
+    pub field: &'static str,
+    /// Value in the previous hotfix.
+    pub old: String,
+    /// Value in the current hotfix.
+    pub new: String,
+}
+
+/// A field that was set to different values in two merged hotfixes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotfixConflict {
+    /// Name of the field, as written in the JSON output.
+    pub field: &'static str,
+    /// Value that was kept.
+    pub kept: String,
+    /// Value that was discarded.
+    pub discarded: String,
 }
 
 impl Hotfix {
+    /// Returns every output field as a (name, value) pair, in declaration order.
+    /// The ports are joined with commas.
+    pub(crate) fn entries(&self) -> [(&'static str, String); 7] {
+        [
+            ("asset_bundle_url", self.asset_bundle_url.clone()),
+            ("ex_resource_url", self.ex_resource_url.clone()),
+            ("lua_url", self.lua_url.clone()),
+            ("ifix_url", self.ifix_url.clone()),
+            (
+                "custom_mdk_res_version",
+                self.custom_mdk_res_version.to_string(),
+            ),
+            ("custom_ifix_version", self.custom_ifix_version.to_string()),
+            (
+                "ports",
+                self.ports
+                    .iter()
+                    .map(u16::to_string)
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+        ]
+    }
+
+    /// Returns the URL of the given resource kind.
+    pub fn url(&self, kind: ResourceKind) -> &str {
+        match kind {
+            ResourceKind::AssetBundle => &self.asset_bundle_url,
+            ResourceKind::ExResource => &self.ex_resource_url,
+            ResourceKind::Lua => &self.lua_url,
+            ResourceKind::Ifix => &self.ifix_url,
+        }
+    }
+
+    /// Returns the resource kinds whose URL wasn't found.
+    pub fn missing_urls(&self) -> Vec<ResourceKind> {
+        ResourceKind::ALL
+            .into_iter()
+            .filter(|&kind| self.url(kind).is_empty())
+            .collect()
+    }
+
+    /// Returns the names of the fields that aren't set, as written in the JSON output: empty URLs and zero versions.
+    /// The ports aren't checked, they aren't needed to download the resources.
+    pub fn missing_fields(&self) -> Vec<&'static str> {
+        self.entries()
+            .into_iter()
+            .filter(|(name, _)| *name != "ports")
+            .filter(|(_, value)| value.is_empty() || value == "0")
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// Returns whether all four URLs and both custom versions are set.
+    pub fn is_complete(&self) -> bool {
+        self.missing_fields().is_empty()
+    }
+
+    /// Returns a mutable reference to the URL of the given resource kind.
+    pub fn url_mut(&mut self, kind: ResourceKind) -> &mut String {
+        match kind {
+            ResourceKind::AssetBundle => &mut self.asset_bundle_url,
+            ResourceKind::ExResource => &mut self.ex_resource_url,
+            ResourceKind::Lua => &mut self.lua_url,
+            ResourceKind::Ifix => &mut self.ifix_url,
+        }
+    }
+
+    /// Fills every empty field of `self` from `other`, without overwriting fields that are already set.
+    /// Returns the fields set in both with different values; `self` keeps its value for those.
+    pub fn merge(&mut self, other: Hotfix) -> Vec<HotfixConflict> {
+        fn merge_field<T: PartialEq + Default + ToString>(
+            field: &'static str,
+            current: &mut T,
+            other: T,
+            conflicts: &mut Vec<HotfixConflict>,
+        ) {
+            if other == T::default() {
+                return;
+            }
+
+            if *current == T::default() {
+                *current = other;
+            } else if *current != other {
+                conflicts.push(HotfixConflict {
+                    field,
+                    kept: current.to_string(),
+                    discarded: other.to_string(),
+                });
+            }
+        }
+
+        let mut conflicts = Vec::new();
+        merge_field(
+            "asset_bundle_url",
+            &mut self.asset_bundle_url,
+            other.asset_bundle_url,
+            &mut conflicts,
+        );
+        merge_field(
+            "ex_resource_url",
+            &mut self.ex_resource_url,
+            other.ex_resource_url,
+            &mut conflicts,
+        );
+        merge_field("lua_url", &mut self.lua_url, other.lua_url, &mut conflicts);
+        merge_field(
+            "ifix_url",
+            &mut self.ifix_url,
+            other.ifix_url,
+            &mut conflicts,
+        );
+        merge_field(
+            "custom_mdk_res_version",
+            &mut self.custom_mdk_res_version,
+            other.custom_mdk_res_version,
+            &mut conflicts,
+        );
+        merge_field(
+            "custom_ifix_version",
+            &mut self.custom_ifix_version,
+            other.custom_ifix_version,
+            &mut conflicts,
+        );
+        merge_field(
+            "access_message",
+            &mut self.access_message,
+            other.access_message,
+            &mut conflicts,
+        );
+        if self.ports.is_empty() {
+            self.ports = other.ports;
+        } else if !other.ports.is_empty() && self.ports != other.ports {
+            conflicts.push(HotfixConflict {
+                field: "ports",
+                kept: format!("{:?}", self.ports),
+                discarded: format!("{:?}", other.ports),
+            });
+        }
+        conflicts
+    }
+
+    /// Compares `self` against a `previous` hotfix and returns every field that changed.
+    pub fn diff(&self, previous: &Hotfix) -> Vec<HotfixChange> {
+        previous
+            .entries()
+            .into_iter()
+            .zip(self.entries())
+            .filter(|((_, old), (_, new))| old != new)
+            .map(|((field, old), (_, new))| HotfixChange { field, old, new })
+            .collect()
+    }
+
+    /// Returns the game version the resource URLs were released for, from the first URL naming one.
+    pub fn release_version(&self) -> Option<String> {
+        ResourceKind::ALL
+            .into_iter()
+            .find_map(|kind| parse_release_version(self.url(kind)))
+    }
+
+    /// Returns true if the gateserver answered with an access message instead of any resource URL,
+    /// which is what happens while the servers are under maintenance.
+    pub fn is_under_maintenance(&self) -> bool {
+        !self.access_message.is_empty()
+            && self.asset_bundle_url.is_empty()
+            && self.ex_resource_url.is_empty()
+            && self.lua_url.is_empty()
+            && self.ifix_url.is_empty()
+    }
+
     /// Create a Hotfix with data from the provided SimpleDecodingResult.
     /// Iterates through the fields and assigns values based on URL patterns.
-    /// Returns a `Hotfix` struct populated with the corresponding URL values and versions.
+    /// Returns a `Hotfix` struct populated with the corresponding URL values and versions,
+    /// along with the inferred proto definition of the message.
     pub fn create_from_simple_message(
-        proto_dec_result: DecodingResult,
-        dispatch: Dispatch,
+        proto_dec_result: &DecodingResult,
+        region_name: &str,
+        patterns: &UrlPatterns,
     ) -> (Self, String) {
+        let (hotfix, fields) = Self::infer_fields(proto_dec_result, region_name, patterns);
+        (hotfix, render_proto(&fields))
+    }
+
+    /// Assigns names to the recognized fields of the gateserver message and fills the hotfix from their values.
+    /// Returns the hotfix along with every recognized field, in the order they were recognized.
+    pub fn infer_fields(
+        proto_dec_result: &DecodingResult,
+        region_name: &str,
+        patterns: &UrlPatterns,
+    ) -> (Self, Vec<InferredField>) {
         let mut hotfix = Hotfix::default();
-        let mut proto_body = String::from("\n");
+        let mut fields = Vec::new();
 
         let mut unk_idx = 1;
         for field in &proto_dec_result.fields {
-            let mut field_content = String::with_capacity(0);
             match field.wire_type {
                 WireType::VarInt => {
                     // We try to find bool that set to "true". Bool represented as varint with value of 1.
                     // We also try to find port, it will be varint other than 1
-                    if let DecodedValue::BigInt(num) = field.value {
+                    if let DecodedValue::UInt(num) = field.value {
                         if num == 1 {
-                            field_content = format!("\tbool unk{unk_idx} = {};\n", field.field);
+                            fields.push(InferredField::new("bool", format!("unk{unk_idx}"), field));
                             unk_idx += 1;
                             // Ensure value is within valid port range
                         } else if (23301..=23302).contains(&num) {
-                            field_content = format!("\tuint32 port = {};\n", field.field);
+                            let name = match hotfix.ports.len() {
+                                0 => String::from("port"),
+                                n => format!("port{}", n + 1),
+                            };
+                            fields.push(InferredField::new("uint32", name, field));
+                            hotfix.ports.push(num as u16);
                         }
                     }
                 }
@@ -57,87 +276,792 @@ impl Hotfix {
 
                     // We try to find the dispatch urls as well as other string fields
                     if let Ok(v) = String::from_utf8(buffer.to_vec()) {
-                        let field_name = match v {
-                            v if v.contains("/asb/") => {
-                                hotfix.asset_bundle_url = v;
-                                "asset_bundle_url"
-                            }
-                            v if v.contains("/design_data/") => {
-                                hotfix.ex_resource_url = v;
-                                "ex_resource_url"
-                            }
-                            v if v.contains("/lua/") => {
-                                hotfix.lua_url = v;
-                                "lua_url"
+                        let field_name = match classify_url(&v, patterns) {
+                            Some(kind) => {
+                                *hotfix.url_mut(kind) = v;
+                                kind.field_name()
                             }
-                            v if v.contains("/ifix/") => {
-                                hotfix.ifix_url = v;
-                                "ifix_url"
-                            }
-                            v if v.contains("Access verification") => "msg",
-                            v if v.eq(&dispatch.region_list[0].name) => "region_name",
-                            v if get_ip_address(&v).is_some() => "ip",
-                            v if is_ec2b_base64(&v) => "client_secret_key",
-                            _ => "",
+                            None => match v {
+                                v if v.contains("Access verification")
+                                    || v.to_lowercase().contains("maintenance") =>
+                                {
+                                    hotfix.access_message = v;
+                                    "msg"
+                                }
+                                v if !region_name.is_empty() && v == region_name => "region_name",
+                                v if get_ip_address(&v).is_some() => "ip",
+                                v if is_ec2b_base64(&v) => "client_secret_key",
+                                _ => "",
+                            },
                         };
 
                         if !field_name.is_empty() {
-                            field_content = format!("\tstring {} = {};\n", field_name, field.field);
+                            fields.push(InferredField::new("string", field_name, field));
                         }
                     }
                 }
                 _ => {}
             }
-            proto_body += &field_content;
         }
 
         // We still have 2 fields left, mdk_res_version (lua_version) and ifix_version, we try to get that from the link we got before
-        let lua_version = hotfix
-            .lua_url
-            .rsplit('/')
-            .next()
-            .unwrap_or_default()
-            .split('_')
-            .nth(1)
-            .unwrap_or_default();
-
-        let ifix_version = hotfix
-            .ifix_url
-            .rsplit('/')
-            .next()
-            .unwrap_or_default()
-            .split('_')
-            .nth(1)
-            .unwrap_or_default();
-
-        for field in proto_dec_result.fields {
-            let mut field_content = String::with_capacity(0);
+        hotfix.custom_mdk_res_version =
+            parse_resource_version(&hotfix.lua_url, patterns).unwrap_or_default();
+        hotfix.custom_ifix_version =
+            parse_resource_version(&hotfix.ifix_url, patterns).unwrap_or_default();
+        let lua_version = hotfix.custom_mdk_res_version.to_string();
+        let ifix_version = hotfix.custom_ifix_version.to_string();
+
+        for field in &proto_dec_result.fields {
             if field.wire_type != WireType::Len {
                 continue;
             }
 
-            let DecodedValue::Buffer(buf) = field.value else {
+            let DecodedValue::Buffer(buf) = &field.value else {
                 continue;
             };
 
-            if let Ok(v) = String::from_utf8(buf) {
+            if let Ok(v) = std::str::from_utf8(buf) {
                 let field_name = match v {
-                    v if v == lua_version => "mdk_res_version",
-                    v if v == ifix_version => "ifix_version",
+                    // The versions are 0 when the URLs are missing, e.g. with `is_need_url=0`
+                    v if hotfix.custom_mdk_res_version != 0 && v == lua_version => {
+                        "mdk_res_version"
+                    }
+                    v if hotfix.custom_ifix_version != 0 && v == ifix_version => "ifix_version",
                     _ => "",
                 };
 
                 if !field_name.is_empty() {
-                    field_content = format!("\tstring {} = {};\n", field_name, field.field);
+                    fields.push(InferredField::new("string", field_name, field));
                 }
             }
+        }
 
-            proto_body += &field_content;
+        if log::log_enabled!(log::Level::Debug) {
+            let table = fields
+                .iter()
+                .map(|field| format!("{:>6} | {}", field.number, field.name))
+                .collect::<Vec<_>>()
+                .join("\n");
+            log::debug!("Inferred gateserver fields:\n field | name\n{table}");
         }
 
-        (
-            hotfix,
-            format!("syntax = \"proto3\";\n\nmessage Gateserver {{{proto_body}}}"),
+        (hotfix, fields)
+    }
+}
+
+/// The build number embedded in `asset_bundle_url`, paired with the `mdk_res_version` field of the gateserver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssetBundleVersion {
+    /// Build number taken from the asset bundle URL, see `parse_resource_version`.
+    pub url_version: Option<u32>,
+    /// Value of the gateserver field inferred as `mdk_res_version`.
+    pub mdk_res_version: Option<u32>,
+    /// False only when both versions are known and differ, which hints at a wrongly extracted URL or field.
+    pub consistent: bool,
+}
+
+impl AssetBundleVersion {
+    /// Reads both versions from the extraction results and compares them.
+    pub fn reconcile(
+        hotfix: &Hotfix,
+        gateserver: &DecodingResult,
+        fields: &[InferredField],
+        patterns: &UrlPatterns,
+    ) -> Self {
+        let url_version = parse_resource_version(&hotfix.asset_bundle_url, patterns);
+        let mdk_res_version = fields
+            .iter()
+            .find(|field| field.name == "mdk_res_version")
+            .and_then(|field| {
+                gateserver
+                    .fields
+                    .iter()
+                    .find(|decoded| decoded.field == field.number)
+            })
+            .and_then(|decoded| match &decoded.value {
+                DecodedValue::Buffer(buf) => std::str::from_utf8(buf).ok()?.parse().ok(),
+                _ => None,
+            });
+
+        Self {
+            url_version,
+            mdk_res_version,
+            consistent: url_version
+                .zip(mdk_res_version)
+                .is_none_or(|(url_version, mdk_res_version)| url_version == mdk_res_version),
+        }
+    }
+}
+
+/// A gateserver field whose meaning was inferred from its value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InferredField {
+    /// Protobuf type of the field, e.g. `string`.
+    pub proto_type: &'static str,
+    /// Name assigned to the field.
+    pub name: String,
+    /// Field number within the gateserver message.
+    pub number: u32,
+    /// Truncated value of the field, quoted for strings.
+    pub sample: String,
+}
+
+impl InferredField {
+    fn new(proto_type: &'static str, name: impl Into<String>, field: &Decoded) -> Self {
+        let sample = match &field.value {
+            DecodedValue::Buffer(_) => format!("{:?}", field.value.preview(48)),
+            value => value.preview(48),
+        };
+
+        Self {
+            proto_type,
+            name: name.into(),
+            number: field.field,
+            sample,
+        }
+    }
+}
+
+/// Default name of the inferred gateserver message.
+pub const DEFAULT_MESSAGE_NAME: &str = "Gateserver";
+
+/// Settings controlling how the inferred proto is rendered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtoOptions {
+    /// Name of the rendered message.
+    pub message_name: String,
+    /// Pad the type and name columns so that the fields line up.
+    pub aligned: bool,
+    /// Follow each field with a comment holding a sample of its value.
+    pub comments: bool,
+}
+
+impl Default for ProtoOptions {
+    fn default() -> Self {
+        Self {
+            message_name: DEFAULT_MESSAGE_NAME.to_string(),
+            aligned: false,
+            comments: false,
+        }
+    }
+}
+
+/// Renders the inferred fields as a proto3 definition of the `Gateserver` message.
+pub fn render_proto(fields: &[InferredField]) -> String {
+    render_proto_with(fields, &ProtoOptions::default())
+}
+
+/// Renders the inferred fields as a proto3 definition, following `options`.
+pub fn render_proto_with(fields: &[InferredField], options: &ProtoOptions) -> String {
+    let (type_width, name_width, number_width) = if options.aligned {
+        fields.iter().fold(
+            (0, 0, 0),
+            |(type_width, name_width, number_width), field| {
+                (
+                    type_width.max(field.proto_type.len()),
+                    name_width.max(field.name.len()),
+                    number_width.max(field.number.to_string().len() + 1),
+                )
+            },
         )
+    } else {
+        (0, 0, 0)
+    };
+
+    let proto_body = fields
+        .iter()
+        .map(|field| {
+            let line = format!(
+                "\t{:<type_width$} {:<name_width$} = ",
+                field.proto_type, field.name
+            );
+            let number = format!("{};", field.number);
+            if options.comments {
+                format!("{line}{number:<number_width$} // {}\n", field.sample)
+            } else {
+                format!("{line}{number}\n")
+            }
+        })
+        .collect::<String>();
+
+    format!(
+        "syntax = \"proto3\";\n\nmessage {} {{\n{proto_body}}}",
+        options.message_name
+    )
+}
+
+/// Renders the top-level fields of `gateserver` as a Rust struct named `message_name`, for bootstrapping a prost decoder.
+/// Inferred fields keep their name and type, the others are named `field_N` and typed after their wire type.
+/// A name already taken by a previous field, e.g. a second `ip`, gets the field number appended, as in `ip_14`.
+/// The prost attributes are written as comments, to be enabled once the struct is moved into a crate depending on prost.
+pub fn render_rust_module(
+    gateserver: &DecodingResult,
+    fields: &[InferredField],
+    message_name: &str,
+) -> String {
+    let mut names = HashSet::new();
+    let struct_body = gateserver
+        .fields_by_number()
+        .into_iter()
+        .filter_map(|(number, occurrences)| {
+            let (prost_type, rust_type, name) =
+                match fields.iter().find(|field| field.number == number) {
+                    Some(field) => {
+                        let rust_type = match field.proto_type {
+                            "bool" => "bool",
+                            "uint32" => "u32",
+                            _ => "String",
+                        };
+                        (field.proto_type, rust_type, field.name.clone())
+                    }
+                    None => {
+                        let (prost_type, rust_type) = match occurrences[0].wire_type {
+                            WireType::VarInt => ("uint64", "u64"),
+                            WireType::I64 => ("fixed64", "u64"),
+                            WireType::I32 => ("fixed32", "u32"),
+                            WireType::Len => ("bytes", "Vec<u8>"),
+                            WireType::SGroup | WireType::EGroup => return None,
+                        };
+                        (prost_type, rust_type, format!("field_{number}"))
+                    }
+                };
+            let name = if names.insert(name.clone()) {
+                name
+            } else {
+                let name = format!("{name}_{number}");
+                names.insert(name.clone());
+                name
+            };
+
+            Some(if occurrences.len() > 1 {
+                format!(
+                    "    // #[prost({prost_type}, repeated, tag = \"{number}\")]\n    pub {name}: Vec<{rust_type}>,\n"
+                )
+            } else {
+                format!(
+                    "    // #[prost({prost_type}, tag = \"{number}\")]\n    pub {name}: {rust_type},\n"
+                )
+            })
+        })
+        .collect::<String>();
+
+    format!(
+        "// #[derive(Clone, PartialEq, ::prost::Message)]\n#[derive(Debug, Clone, PartialEq, Default)]\npub struct {message_name} {{\n{struct_body}}}\n"
+    )
+}
+
+/// Parses and typechecks `proto` with protobuf-parse, returning its error message if `proto` is not a valid proto file,
+/// e.g. because two fields share a name or a number.
+#[cfg(feature = "validate-proto")]
+pub fn validate_proto(proto: &str) -> Result<(), String> {
+    // protobuf-parse only reads files, so the proto goes through a scratch directory, unique to each call
+    static CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    let call = CALLS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("fetch-hotfix-{}-{call}", std::process::id()));
+    let path = dir.join("Gateserver.proto");
+    let result = std::fs::create_dir_all(&dir)
+        .and_then(|()| std::fs::write(&path, proto))
+        .map_err(|e| e.to_string())
+        .and_then(|()| {
+            protobuf_parse::Parser::new()
+                .pure()
+                .include(&dir)
+                .input(&path)
+                .parse_and_typecheck()
+                .map(|_| ())
+                .map_err(|e| format!("{e:#}"))
+        });
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+/// Returns whether `name` can be used as a proto message name: a letter or `_` followed by letters, digits or `_`.
+pub fn is_valid_proto_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        decode::Decoder,
+        test_util::{
+            ASSET_BUNDLE_URL, LUA_URL, REGION_NAME, capture_logs, gateserver_message,
+            push_len_field, push_varint_field,
+        },
+    };
+
+    fn infer(message: Vec<u8>) -> (Hotfix, Vec<InferredField>) {
+        let gateserver = Decoder::new(message).decode().unwrap();
+        Hotfix::infer_fields(&gateserver, REGION_NAME, &UrlPatterns::default())
+    }
+
+    #[test]
+    fn detects_maintenance_responses() {
+        let mut message = Vec::new();
+        push_len_field(&mut message, 1, REGION_NAME.as_bytes());
+        push_len_field(
+            &mut message,
+            11,
+            b"Server maintenance in progress, please try again later",
+        );
+
+        let (hotfix, fields) = infer(message);
+
+        assert!(hotfix.is_under_maintenance());
+        assert!(hotfix.access_message.contains("maintenance"));
+        assert!(fields.iter().any(|field| field.name == "msg"));
+    }
+
+    #[test]
+    fn responses_with_urls_are_not_under_maintenance() {
+        let (hotfix, _) = infer(gateserver_message());
+
+        assert!(!hotfix.is_under_maintenance());
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let (hotfix, _) = infer(gateserver_message());
+
+        let json = serde_json::to_string(&hotfix).unwrap();
+
+        assert_eq!(serde_json::from_str::<Hotfix>(&json).unwrap(), hotfix);
+    }
+
+    #[test]
+    fn diff_reports_the_changed_url() {
+        let (previous, _) = infer(gateserver_message());
+        let mut hotfix = previous.clone();
+        hotfix.lua_url = String::from(
+            "https://autopatchcn.bhsr.com/lua/V2.2Live/output_7600000_0f1e2d3c4b5a/client",
+        );
+
+        let changes = hotfix.diff(&previous);
+
+        assert_eq!(
+            changes,
+            [HotfixChange {
+                field: "lua_url",
+                old: previous.lua_url.clone(),
+                new: hotfix.lua_url.clone(),
+            }]
+        );
+        assert!(previous.diff(&previous).is_empty());
+    }
+
+    #[test]
+    fn logs_the_inferred_field_table() {
+        let logs = capture_logs(|| {
+            infer(gateserver_message());
+        });
+
+        let table = logs
+            .iter()
+            .find(|record| record.starts_with("DEBUG Inferred gateserver fields:"))
+            .unwrap();
+        assert!(table.contains("\n field | name\n"));
+        assert!(table.contains("\n     6 | lua_url"));
+        assert!(table.contains("\n     3 | port"));
+    }
+
+    #[test]
+    fn merge_fills_empty_fields() {
+        let (complete, _) = infer(gateserver_message());
+        let mut hotfix = Hotfix {
+            lua_url: complete.lua_url.clone(),
+            custom_ifix_version: 7570000,
+            ..Default::default()
+        };
+
+        let conflicts = hotfix.merge(complete.clone());
+
+        assert!(conflicts.is_empty());
+        assert_eq!(
+            hotfix,
+            Hotfix {
+                custom_ifix_version: 7570000,
+                ..complete
+            }
+        );
+    }
+
+    #[test]
+    fn merge_keeps_its_own_value_on_conflict() {
+        let (mut hotfix, _) = infer(gateserver_message());
+        let original = hotfix.clone();
+        let other = Hotfix {
+            lua_url: String::from("https://example.com/lua/output_1_a/client"),
+            ports: vec![23302],
+            ..Default::default()
+        };
+
+        let conflicts = hotfix.merge(other);
+
+        assert_eq!(hotfix, original);
+        assert_eq!(
+            conflicts,
+            [
+                HotfixConflict {
+                    field: "lua_url",
+                    kept: original.lua_url.clone(),
+                    discarded: String::from("https://example.com/lua/output_1_a/client"),
+                },
+                HotfixConflict {
+                    field: "ports",
+                    kept: String::from("[23301]"),
+                    discarded: String::from("[23302]"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn schema_lists_the_output_fields() {
+        let schema = serde_json::to_value(schemars::schema_for!(Hotfix)).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+
+        for name in [
+            "asset_bundle_url",
+            "ex_resource_url",
+            "lua_url",
+            "ifix_url",
+            "custom_mdk_res_version",
+            "custom_ifix_version",
+            "access_message",
+            "ports",
+        ] {
+            assert!(properties.contains_key(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn uses_the_chosen_message_name() {
+        let (_, fields) = infer(gateserver_message());
+        let options = ProtoOptions {
+            message_name: String::from("RegionGateserver"),
+            ..Default::default()
+        };
+
+        let proto = render_proto_with(&fields, &options);
+
+        assert!(proto.contains("message RegionGateserver {"));
+        assert!(!proto.contains("message Gateserver {"));
+    }
+
+    #[test]
+    fn validates_proto_identifiers() {
+        for name in ["Gateserver", "_Gateserver2", "gate_server"] {
+            assert!(is_valid_proto_identifier(name), "{name}");
+        }
+        for name in [
+            "",
+            "2Gateserver",
+            "Gate-server",
+            "Gate server",
+            "Gäteserver",
+        ] {
+            assert!(!is_valid_proto_identifier(name), "{name}");
+        }
+    }
+
+    fn field(proto_type: &'static str, name: &str, number: u32) -> InferredField {
+        InferredField {
+            proto_type,
+            name: name.to_string(),
+            number,
+            sample: String::new(),
+        }
+    }
+
+    #[test]
+    fn aligns_the_columns_of_the_fields() {
+        let fields = [
+            field("string", "lua_url", 6),
+            field("uint32", "port", 3),
+            field("string", "asset_bundle_url", 14),
+        ];
+        let options = ProtoOptions {
+            aligned: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            render_proto_with(&fields, &options),
+            "syntax = \"proto3\";\n\nmessage Gateserver {\n\
+             \tstring lua_url          = 6;\n\
+             \tuint32 port             = 3;\n\
+             \tstring asset_bundle_url = 14;\n}"
+        );
+        assert_eq!(
+            render_proto(&fields),
+            "syntax = \"proto3\";\n\nmessage Gateserver {\n\
+             \tstring lua_url = 6;\n\
+             \tuint32 port = 3;\n\
+             \tstring asset_bundle_url = 14;\n}"
+        );
+    }
+
+    #[test]
+    fn release_version_comes_from_the_resource_urls() {
+        let (mut hotfix, _) = infer(gateserver_message());
+        assert_eq!(hotfix.release_version().as_deref(), Some("V2.2Live"));
+
+        for kind in ResourceKind::ALL {
+            hotfix.url_mut(kind).clear();
+        }
+        assert_eq!(hotfix.release_version(), None);
+    }
+
+    #[test]
+    fn complete_hotfixes_miss_nothing() {
+        let (hotfix, _) = infer(gateserver_message());
+
+        assert!(hotfix.is_complete());
+        assert!(hotfix.missing_fields().is_empty());
+    }
+
+    #[test]
+    fn custom_versions_come_from_the_resource_urls() {
+        let (hotfix, _) = infer(gateserver_message());
+
+        assert_eq!(hotfix.custom_mdk_res_version, 7576543);
+        assert_eq!(hotfix.custom_ifix_version, 7570000);
+    }
+
+    #[test]
+    fn reports_the_missing_fields_of_partial_hotfixes() {
+        let (mut hotfix, _) = infer(gateserver_message());
+        hotfix.ifix_url.clear();
+        hotfix.custom_ifix_version = 0;
+        hotfix.ports.clear();
+
+        assert!(!hotfix.is_complete());
+        assert_eq!(hotfix.missing_fields(), ["ifix_url", "custom_ifix_version"]);
+        assert_eq!(hotfix.missing_urls(), [ResourceKind::Ifix]);
+
+        // The custom versions are taken from the URLs, so they are missing along with them
+        let mut message = Vec::new();
+        push_len_field(&mut message, 1, REGION_NAME.as_bytes());
+        push_varint_field(&mut message, 3, 23301);
+        push_len_field(&mut message, 6, LUA_URL.as_bytes());
+        let (inferred, _) = infer(message);
+        assert_eq!(
+            inferred.missing_fields(),
+            [
+                "asset_bundle_url",
+                "ex_resource_url",
+                "ifix_url",
+                "custom_ifix_version"
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_each_field_with_a_sample() {
+        let (_, fields) = infer(gateserver_message());
+        let options = ProtoOptions {
+            comments: true,
+            ..Default::default()
+        };
+
+        let proto = render_proto_with(&fields, &options);
+
+        assert!(proto.contains(
+            "\tstring lua_url = 6; // \"https://autopatchcn.bhsr.com/lua/V2.2Live/output...\"\n"
+        ));
+        assert!(proto.contains("\tuint32 port = 3; // 23301\n"));
+        assert!(!render_proto(&fields).contains("//"));
+    }
+
+    #[test]
+    fn aligns_the_comments_of_aligned_protos() {
+        let fields = [
+            InferredField {
+                sample: String::from("23301"),
+                ..field("uint32", "port", 3)
+            },
+            InferredField {
+                sample: String::from("\"10.0.0.1\""),
+                ..field("string", "ip", 14)
+            },
+        ];
+        let options = ProtoOptions {
+            aligned: true,
+            comments: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            render_proto_with(&fields, &options),
+            "syntax = \"proto3\";\n\nmessage Gateserver {\n\
+             \tuint32 port = 3;  // 23301\n\
+             \tstring ip   = 14; // \"10.0.0.1\"\n}"
+        );
+    }
+
+    #[test]
+    fn renders_a_rust_struct_with_the_inferred_names() {
+        let mut message = gateserver_message();
+        push_varint_field(&mut message, 20, 7);
+        push_varint_field(&mut message, 20, 8);
+        message.extend_from_slice(&[0xAD, 0x01, 0x01, 0x02, 0x03, 0x04]); // Field 21 as fixed32
+        let gateserver = Decoder::new(message).decode().unwrap();
+        let (_, fields) = Hotfix::infer_fields(&gateserver, REGION_NAME, &UrlPatterns::default());
+
+        let module = render_rust_module(&gateserver, &fields, "Gateserver");
+
+        assert!(module.starts_with(
+            "// #[derive(Clone, PartialEq, ::prost::Message)]\n\
+             #[derive(Debug, Clone, PartialEq, Default)]\n\
+             pub struct Gateserver {\n"
+        ));
+        for expected in [
+            "    // #[prost(string, tag = \"1\")]\n    pub region_name: String,\n",
+            "    // #[prost(string, tag = \"2\")]\n    pub ip: String,\n",
+            "    // #[prost(uint32, tag = \"3\")]\n    pub port: u32,\n",
+            "    // #[prost(string, tag = \"6\")]\n    pub lua_url: String,\n",
+            "    // #[prost(string, tag = \"8\")]\n    pub mdk_res_version: String,\n",
+            "    // #[prost(uint64, repeated, tag = \"20\")]\n    pub field_20: Vec<u64>,\n",
+            "    // #[prost(fixed32, tag = \"21\")]\n    pub field_21: u32,\n",
+        ] {
+            assert!(module.contains(expected), "{expected}");
+        }
+        assert!(module.ends_with("}\n"));
+    }
+
+    #[test]
+    fn renders_unique_rust_field_names() {
+        let mut message = gateserver_message();
+        push_len_field(&mut message, 14, b"10.0.0.2");
+        let gateserver = Decoder::new(message).decode().unwrap();
+        let (_, fields) = Hotfix::infer_fields(&gateserver, REGION_NAME, &UrlPatterns::default());
+
+        let module = render_rust_module(&gateserver, &fields, "Gateserver");
+
+        assert!(module.contains("    // #[prost(string, tag = \"2\")]\n    pub ip: String,\n"));
+        assert!(module.contains("    // #[prost(string, tag = \"14\")]\n    pub ip_14: String,\n"));
+    }
+
+    fn asset_bundle_version(message: Vec<u8>) -> AssetBundleVersion {
+        let gateserver = Decoder::new(message).decode().unwrap();
+        let patterns = UrlPatterns::default();
+        let (hotfix, fields) = Hotfix::infer_fields(&gateserver, REGION_NAME, &patterns);
+        AssetBundleVersion::reconcile(&hotfix, &gateserver, &fields, &patterns)
+    }
+
+    #[test]
+    fn asset_bundle_version_matching_mdk_res_version_is_consistent() {
+        assert_eq!(
+            asset_bundle_version(gateserver_message()),
+            AssetBundleVersion {
+                url_version: Some(7576543),
+                mdk_res_version: Some(7576543),
+                consistent: true,
+            }
+        );
+    }
+
+    #[test]
+    fn asset_bundle_version_differing_from_mdk_res_version_is_inconsistent() {
+        // The asset bundle URL is for another build than LUA_URL and mdk_res_version
+        let mut message = Vec::new();
+        push_len_field(&mut message, 1, REGION_NAME.as_bytes());
+        push_len_field(
+            &mut message,
+            4,
+            b"https://autopatchcn.bhsr.com/asb/V2.2Live/output_7563532_5c6f0d11b0e5/client",
+        );
+        push_len_field(&mut message, 6, LUA_URL.as_bytes());
+        push_len_field(&mut message, 8, b"7576543");
+
+        assert_eq!(
+            asset_bundle_version(message),
+            AssetBundleVersion {
+                url_version: Some(7563532),
+                mdk_res_version: Some(7576543),
+                consistent: false,
+            }
+        );
+    }
+
+    #[test]
+    fn asset_bundle_version_missing_a_value_is_consistent() {
+        let mut message = Vec::new();
+        push_len_field(&mut message, 1, REGION_NAME.as_bytes());
+        push_len_field(&mut message, 4, ASSET_BUNDLE_URL.as_bytes());
+        push_len_field(&mut message, 6, LUA_URL.as_bytes());
+        // Not the build of LUA_URL, so it isn't inferred as mdk_res_version
+        push_len_field(&mut message, 8, b"7576000");
+        let version = asset_bundle_version(message);
+
+        assert_eq!(version.url_version, Some(7576543));
+        assert_eq!(version.mdk_res_version, None);
+        assert!(version.consistent);
+    }
+
+    #[test]
+    fn collects_both_ports_and_labels_the_primary_one() {
+        let mut message = gateserver_message();
+        push_varint_field(&mut message, 9, 23302);
+
+        let (hotfix, fields) = infer(message);
+
+        assert_eq!(hotfix.ports, [23301, 23302]);
+        let port_fields = fields
+            .iter()
+            .filter(|field| field.name.starts_with("port"))
+            .map(|field| (field.name.as_str(), field.number))
+            .collect::<Vec<_>>();
+        assert_eq!(port_fields, [("port", 3), ("port2", 9)]);
+        assert!(
+            Hotfix {
+                ports: Vec::new(),
+                ..hotfix.clone()
+            }
+            .is_complete()
+        );
+        assert_eq!(
+            hotfix.entries().last().unwrap(),
+            &("ports", String::from("23301,23302"))
+        );
+
+        // Losing the second port is a change
+        let previous = Hotfix {
+            ports: vec![23301],
+            ..hotfix.clone()
+        };
+        let changes = hotfix.diff(&previous);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].field, "ports");
+        assert_eq!(changes[0].old, "23301");
+    }
+
+    #[cfg(feature = "validate-proto")]
+    #[test]
+    fn validation_accepts_the_inferred_proto() {
+        let (_, fields) = infer(gateserver_message());
+
+        assert_eq!(validate_proto(&render_proto(&fields)), Ok(()));
+    }
+
+    #[cfg(feature = "validate-proto")]
+    #[test]
+    fn validation_rejects_colliding_fields() {
+        let same_name = [field("string", "lua_url", 6), field("string", "lua_url", 7)];
+        let same_number = [
+            field("string", "lua_url", 6),
+            field("string", "ifix_url", 6),
+        ];
+
+        for fields in [same_name, same_number] {
+            let proto = render_proto(&fields);
+            assert!(validate_proto(&proto).is_err(), "{proto}");
+        }
     }
 }
@@ -0,0 +1,139 @@
+use std::{fmt, str::FromStr};
+
+/// Language of the client, sent as the `language_type` query parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub enum Language {
+    Chs,
+    Cht,
+    #[default]
+    En,
+    Kr,
+    Jp,
+    Fr,
+    De,
+    Es,
+    Pt,
+    Ru,
+    Th,
+    Vi,
+    Id,
+}
+
+impl Language {
+    /// Value of the `language_type` query parameter for this language.
+    pub fn language_type(self) -> u32 {
+        match self {
+            Language::Chs => 1,
+            Language::Cht => 2,
+            Language::En => 3,
+            Language::Kr => 4,
+            Language::Jp => 5,
+            Language::Fr => 6,
+            Language::De => 7,
+            Language::Es => 8,
+            Language::Pt => 9,
+            Language::Ru => 10,
+            Language::Th => 11,
+            Language::Vi => 12,
+            Language::Id => 13,
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "chs" | "zh" | "zh-cn" => Ok(Language::Chs),
+            "cht" | "zh-tw" => Ok(Language::Cht),
+            "en" => Ok(Language::En),
+            "kr" | "ko" => Ok(Language::Kr),
+            "jp" | "ja" => Ok(Language::Jp),
+            "fr" => Ok(Language::Fr),
+            "de" => Ok(Language::De),
+            "es" => Ok(Language::Es),
+            "pt" => Ok(Language::Pt),
+            "ru" => Ok(Language::Ru),
+            "th" => Ok(Language::Th),
+            "vi" => Ok(Language::Vi),
+            "id" => Ok(Language::Id),
+            _ => Err(format!("unknown language: {s}")),
+        }
+    }
+}
+
+impl TryFrom<String> for Language {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Language::Chs => "chs",
+            Language::Cht => "cht",
+            Language::En => "en",
+            Language::Kr => "kr",
+            Language::Jp => "jp",
+            Language::Fr => "fr",
+            Language::De => "de",
+            Language::Es => "es",
+            Language::Pt => "pt",
+            Language::Ru => "ru",
+            Language::Th => "th",
+            Language::Vi => "vi",
+            Language::Id => "id",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_each_language_name_to_its_code() {
+        let expected = [
+            ("chs", 1),
+            ("cht", 2),
+            ("en", 3),
+            ("kr", 4),
+            ("jp", 5),
+            ("fr", 6),
+            ("de", 7),
+            ("es", 8),
+            ("pt", 9),
+            ("ru", 10),
+            ("th", 11),
+            ("vi", 12),
+            ("id", 13),
+        ];
+
+        for (name, language_type) in expected {
+            let language: Language = name.parse().unwrap();
+            assert_eq!(language.language_type(), language_type, "{name}");
+            assert_eq!(language.to_string(), name);
+        }
+    }
+
+    #[test]
+    fn defaults_to_english() {
+        assert_eq!(Language::default().language_type(), 3);
+    }
+
+    #[test]
+    fn parses_aliases_and_rejects_unknown_names() {
+        assert_eq!("ZH".parse(), Ok(Language::Chs));
+        assert_eq!("ja".parse(), Ok(Language::Jp));
+        assert_eq!("ko".parse(), Ok(Language::Kr));
+        assert_eq!(
+            "klingon".parse::<Language>(),
+            Err(String::from("unknown language: klingon"))
+        );
+    }
+}
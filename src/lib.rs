@@ -0,0 +1,17 @@
+pub mod base64;
+pub mod binary_version;
+pub mod client_config;
+pub mod decode;
+pub mod error;
+pub mod fetch;
+pub mod hotfix;
+pub mod language;
+pub mod metadata;
+pub mod output;
+pub mod params;
+pub mod platform;
+pub mod proto;
+pub mod resource;
+#[cfg(test)]
+mod test_util;
+pub mod util;
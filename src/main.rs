@@ -1,114 +1,1074 @@
-use prost::Message;
-use reqwest::Client;
+use std::fs;
 use std::io::Write;
-use std::time::Instant;
-use std::{fs, path::PathBuf};
-
-mod proto;
-use proto::Dispatch;
-mod decode;
-use decode::Decoder;
-mod binary_version;
-mod client_config;
-mod hotfix;
-
-use hotfix::Hotfix;
-mod util;
-use binary_version::BinaryVersionData;
-use client_config::ClientStartupConfig;
-use util::{get_binary_version_path, get_client_config_path, select_folder};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+mod cli;
+mod config;
+use cli::{Args, Command};
+
+use fetch_hotfix::binary_version::BinaryVersionData;
+use fetch_hotfix::client_config::ClientStartupConfig;
+use fetch_hotfix::decode::DecodingResult;
+use fetch_hotfix::error::AppError;
+use fetch_hotfix::fetch::{
+    FetchOutput, ProgressEvent, build_client, decode_gateserver, decode_gateway_response,
+    fetch_hotfix_with_fallback, verify_urls,
+};
+use fetch_hotfix::hotfix::{Hotfix, render_proto_with, render_rust_module};
+use fetch_hotfix::metadata::Metadata;
+use fetch_hotfix::output::{
+    BatchState, DispatchJson, HistoryEntry, append_history, check_wire_baseline,
+    save_proto_snapshot, source_hash, write_fields_csv, write_protoc_bundle,
+};
+use fetch_hotfix::resource::ResourceKind;
+use fetch_hotfix::util::{
+    get_binary_version_path, get_client_config_path, missing_game_files, read_capped,
+    select_folder, versions_match,
+};
+
+/// Exit code used when the servers are under maintenance.
+const EXIT_MAINTENANCE: i32 = 2;
+/// Exit code used when `--diff` found changes.
+const EXIT_DIFFERENT: i32 = 3;
+/// Version naming the output files of a decoded response when `--game-version` isn't passed.
+const UNKNOWN_VERSION: &str = "unknown";
+
+/// Asks for the game folder until one holding the game files is selected, or the selection is canceled.
+fn select_game_folder(args: &Args) -> Option<PathBuf> {
+    let mut folder_path = select_folder(args.start_dir.as_deref())?;
+
+    loop {
+        let missing = missing_game_files(&folder_path, args.assets_subpath());
+        if missing.is_empty() {
+            return Some(folder_path);
+        }
+
+        for path in &missing {
+            eprintln!("->> Missing {}", path.display());
+        }
+        eprintln!(
+            "->> {} is not a HSR folder, select another one.",
+            folder_path.display()
+        );
+
+        folder_path = select_folder(Some(&folder_path))?;
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(folder_path) = select_folder() {
-        let start_time = Instant::now();
+    let args = Args::load()?;
+
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    if args.emit_schema {
+        let schema = schemars::schema_for!(Hotfix);
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        return Ok(());
+    }
+
+    if let Some(Command::Version { path }) = &args.command {
+        print_version(&mut std::io::stdout().lock(), &args, path)?;
+        return Ok(());
+    }
+
+    let start_time = Instant::now();
+
+    // Responses passed on the command line are decoded without reading the game files or querying the servers
+    if let Some(output) = decode_input(&args)? {
+        let game_version = args.game_version.as_deref().unwrap_or(UNKNOWN_VERSION);
+        if let Some(code) = write_output(&args, &output, game_version, start_time)? {
+            std::process::exit(code);
+        }
+        return Ok(());
+    }
+
+    if !args.batch.is_empty() {
+        let written = run_batch(&args).await?;
+        eprintln!("->> Wrote the output of {written} versions");
+        return Ok(());
+    }
+
+    if let Some(folder_path) = select_game_folder(&args) {
+        let game = read_game_files(&args, &folder_path)?;
+
+        if let Some(secs) = args.watch {
+            return watch(&args, &game, Duration::from_secs(secs)).await;
+        }
+
+        let Some(output) = fetch_output(&args, &game).await? else {
+            eprintln!("->> region_list is empty.");
+            return Ok(());
+        };
+
+        if let Some(hotfix_version) = output.hotfix.release_version() {
+            let installed_version = game.binary_version.game_version();
+            if !versions_match(&hotfix_version, &installed_version) {
+                log::warn!(
+                    "The hotfix is for version {hotfix_version}, but the installed build is {installed_version}"
+                );
+            }
+        }
+
+        if args.verify {
+            verify_output(&args, &output).await?;
+        }
+
+        if let Some(code) = write_output(&args, &output, &game.game_version, start_time)? {
+            std::process::exit(code);
+        }
+
+        Ok(())
+    } else {
+        eprintln!("->> No folder selected.");
+
+        Ok(())
+    }
+}
+
+/// The game files read from the selected folder.
+struct GameFiles {
+    client_config: ClientStartupConfig,
+    binary_version: BinaryVersionData,
+    /// Version sent to the dispatch, see `BinaryVersionData::get_server_pak_type_version`.
+    game_version: String,
+}
+
+/// Reads the game files from the game folder `folder_path`.
+fn read_game_files(
+    args: &Args,
+    folder_path: &std::path::Path,
+) -> Result<GameFiles, Box<dyn std::error::Error>> {
+    let binary_version_path = get_binary_version_path(folder_path, args.assets_subpath());
+    let client_config_path = get_client_config_path(folder_path, args.assets_subpath());
+
+    let client_config_buffer = read_capped(&client_config_path, args.max_file_size)?;
+    let client_config = ClientStartupConfig::try_from(client_config_buffer)?;
+
+    let binary_version_buffer = read_capped(&binary_version_path, args.max_file_size)?;
+    let binary_version = BinaryVersionData::try_from(binary_version_buffer)?;
+
+    let game_version = binary_version
+        .get_server_pak_type_version()
+        .ok_or(AppError::MissingGameVersion)?;
+
+    eprintln!("->> Version: {}", binary_version.version_string);
+    eprintln!("->> Build: {}", binary_version.branch);
+
+    if args.unknowns_both_endian {
+        eprint!("{}", binary_version.describe_unknowns());
+    }
+
+    if args.insecure {
+        eprintln!("!! WARNING: TLS certificate verification is disabled (--insecure) !!");
+    }
+
+    Ok(GameFiles {
+        client_config,
+        binary_version,
+        game_version,
+    })
+}
+
+/// Fetches the hotfix of every `--batch` folder in turn, recording each completed version in the `--batch-state` file.
+/// Versions completed by an earlier run are skipped unless `--force` is passed, so that an interrupted run can be resumed.
+/// Returns the number of versions whose output was written.
+async fn run_batch(args: &Args) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut state = BatchState::read(&args.batch_state)?;
+    let mut written = 0;
+
+    for folder_path in &args.batch {
+        eprintln!("->> Folder: {}", folder_path.display());
+        let game = read_game_files(args, folder_path)?;
+        if !args.force && state.is_completed(&game.game_version) {
+            eprintln!("->> {} already completed, skipping", game.game_version);
+            continue;
+        }
+
+        let Some(output) = fetch_output(args, &game).await? else {
+            eprintln!("->> region_list is empty.");
+            continue;
+        };
 
-        let binary_version_path = get_binary_version_path(&folder_path);
-        let client_config_path = get_client_config_path(&folder_path);
+        match write_output(args, &output, &game.game_version, Instant::now())? {
+            None | Some(EXIT_DIFFERENT) => {
+                state.mark_completed(&args.batch_state, &game.game_version)?;
+                written += 1;
+            }
+            // Nothing was written, e.g. during maintenance, so the version is fetched again by the next run
+            Some(code) => log::info!(
+                "Output of {} not written (exit code {code})",
+                game.game_version
+            ),
+        }
+    }
+
+    Ok(written)
+}
 
-        let client_config_buffer = fs::read(&client_config_path)?;
-        let client_config = ClientStartupConfig::try_from(client_config_buffer)?;
+/// Decodes the gateserver response passed with `--hex-input`, `--gateway-file` or `--from-clipboard`, if any.
+fn decode_input(args: &Args) -> Result<Option<FetchOutput>, Box<dyn std::error::Error>> {
+    if let Some(message) = args.hex_input()? {
+        return Ok(Some(decode_gateserver(message, Metadata::default())?));
+    }
+
+    if let Some(response) = args.gateway_input()? {
+        let output = decode_gateway_response(&response, Metadata::default(), &args.envelope_field)?;
+        return Ok(Some(output));
+    }
 
-        let binary_version_buffer = fs::read(&binary_version_path)?;
-        let binary_version = BinaryVersionData::try_from(binary_version_buffer)?;
+    Ok(None)
+}
 
-        let game_version = binary_version
-            .get_server_pak_type_version()
-            .expect("cannot find game version!");
+/// Fetches the hotfix from the servers listed in the game files.
+/// Returns `None` if the dispatch doesn't list any region.
+async fn fetch_output(
+    args: &Args,
+    game: &GameFiles,
+) -> Result<Option<FetchOutput>, Box<dyn std::error::Error>> {
+    let GameFiles {
+        client_config,
+        binary_version,
+        game_version,
+    } = game;
 
-        println!("->> Version: {}", binary_version.version_string);
-        println!("->> Build: {}", binary_version.branch);
+    let params = args.dispatch_params(game_version);
 
-        let query_dispatch_url = format!(
-            "{}?version={}&language_type=3&platform_type=3&channel_id=1&sub_channel_id=1&is_new_format=1",
-            client_config
-                .global_dispatch_url_list
-                .first()
-                .expect("cannot found dispatch url!"),
-            game_version
+    let dispatch_urls = match &args.dispatch_url {
+        Some(dispatch_url) => std::slice::from_ref(dispatch_url),
+        None => client_config.global_dispatch_url_list.as_slice(),
+    };
+
+    let client = &build_client(&args.client_options()?)?;
+
+    let mut report = |event: ProgressEvent| match event {
+        ProgressEvent::FetchingDispatch { url } => eprintln!("->> Dispatch URL: {url}"),
+        ProgressEvent::FetchingGateway { url, .. } => eprintln!("->> Gateway URL: {url}"),
+        _ => {}
+    };
+    let fetch = fetch_hotfix_with_fallback(
+        client,
+        dispatch_urls,
+        &binary_version.dispatch_seed,
+        &params,
+        args.retry_policy(),
+        &mut report,
+    );
+    let result = match args.deadline {
+        Some(secs) => tokio::time::timeout(Duration::from_secs(secs), fetch)
+            .await
+            .map_err(|_| AppError::Timeout)?,
+        None => fetch.await,
+    };
+
+    Ok(result?)
+}
+
+/// Checks that the resource URLs of the hotfix are reachable, for `--verify`, warning about those that aren't.
+async fn verify_output(
+    args: &Args,
+    output: &FetchOutput,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = build_client(&args.client_options()?)?;
+    let checks = verify_urls(&client, &output.hotfix, args.verify_concurrency).await;
+
+    for check in &checks {
+        let name = check.kind.field_name();
+        match &check.result {
+            Ok(status) if check.is_reachable() => eprintln!("->> {name}: {status}"),
+            Ok(status) => {
+                log::warn!("{name} is unreachable: {} answered {status}", check.url)
+            }
+            Err(e) => log::warn!("{name} is unreachable: {e}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the fetched hotfix and writes the output files.
+/// Returns the code to exit with when the servers are under maintenance or `--diff` found changes.
+fn write_output(
+    args: &Args,
+    output: &FetchOutput,
+    game_version: &str,
+    start_time: Instant,
+) -> Result<Option<i32>, Box<dyn std::error::Error>> {
+    if args.raw_output {
+        write_raw_output(&mut std::io::stdout().lock(), &output.gateserver)?;
+        return Ok(None);
+    }
+
+    // Read before writing anything, as the previous hotfix may be the file about to be overwritten
+    let previous = match &args.diff {
+        Some(previous_path) => {
+            let previous: Hotfix = serde_json::from_str(&fs::read_to_string(previous_path)?)?;
+            Some((previous_path, previous))
+        }
+        None => None,
+    };
+
+    if let Some(path) = &args.wire_baseline {
+        for deviation in check_wire_baseline(
+            path,
+            game_version,
+            &output.gateserver,
+            args.wire_baseline_threshold,
+        )? {
+            log::warn!("Gateserver layout may have changed: {deviation}");
+        }
+    }
+
+    if output.hotfix.is_under_maintenance() {
+        eprintln!(
+            "->> Server is under maintenance: {}",
+            output.hotfix.access_message
         );
+        return Ok(Some(EXIT_MAINTENANCE));
+    }
+
+    if let Some(kind) = args.only {
+        write_only_url(&mut std::io::stdout().lock(), &output.hotfix, kind)?;
+        return Ok(None);
+    }
 
-        println!("->> Dispatch URL: {}", query_dispatch_url);
+    let present = output.gateserver.fields_by_number();
+    let absent = args
+        .require_fields
+        .iter()
+        .filter(|number| !present.contains_key(number))
+        .map(|number| format!("#{number}"))
+        .collect::<Vec<_>>();
+    if !absent.is_empty() {
+        return Err(AppError::MissingFields(absent.join(", ")).into());
+    }
 
-        let client = &Client::new();
+    let missing = output.hotfix.missing_urls();
+    if !missing.is_empty() {
+        let names = missing
+            .iter()
+            .map(|kind| kind.field_name())
+            .collect::<Vec<_>>()
+            .join(", ");
 
-        let query_dispatch_response = client.get(&query_dispatch_url).send().await?.text().await?;
+        // The gateway omits the URLs on purpose when queried with `is_need_url=0`
+        if args.no_need_url {
+            log::info!("Missing {names}, as requested with --no-need-url");
+        } else if !args.keep_going {
+            return Err(AppError::MissingFields(names).into());
+        } else {
+            log::warn!("Missing {names}, writing partial output");
+        }
+    }
 
-        let dispatch_decoded_base64 = rbase64::decode(&query_dispatch_response)?;
+    let proto = render_proto_with(&output.fields, &args.proto_options());
+    #[cfg(feature = "validate-proto")]
+    fetch_hotfix::hotfix::validate_proto(&proto).map_err(AppError::InvalidProto)?;
 
-        let dispatch_decoded_message = Dispatch::decode(&*dispatch_decoded_base64)?;
+    fs::create_dir_all(&args.out_dir)?;
 
-        if dispatch_decoded_message.region_list.is_empty() {
-            println!("->> region_list is empty.");
-            return Ok(());
+    let format = args.output_format();
+    let hotfix_text = format.render(&output.hotfix)?;
+
+    let output_path = args
+        .out_dir
+        .join(format!("hotfix-{}.{}", game_version, format.extension()));
+
+    let mut file = fs::File::create(output_path)?;
+
+    file.write_all(hotfix_text.as_bytes())?;
+
+    eprintln!("->> Finished writing hotfix.{}", format.extension());
+
+    let metadata_json = args.to_json(&output.metadata)?;
+
+    let output_path = args
+        .out_dir
+        .join(format!("hotfix-{}.meta.json", game_version));
+
+    let mut file = fs::File::create(output_path)?;
+
+    file.write_all(metadata_json.as_bytes())?;
+
+    eprintln!("->> Finished writing hotfix.meta.json");
+
+    if args.save_dispatch
+        && let Some(dispatch) = &output.dispatch
+    {
+        let output_path = args
+            .out_dir
+            .join(format!("hotfix-{}.dispatch.json", game_version));
+        fs::write(output_path, args.to_json(&DispatchJson::from(dispatch))?)?;
+        eprintln!("->> Finished writing hotfix.dispatch.json");
+    }
+
+    let output_path = args
+        .out_dir
+        .join(format!("Gateserver-{}.proto", game_version));
+
+    let mut file = fs::File::create(output_path)?;
+
+    file.write_all(proto.as_bytes())?;
+
+    eprintln!("->> Finished writing Gateserver.proto");
+
+    if let Some(csv_path) = &args.csv {
+        write_fields_csv(csv_path, &output.gateserver, &output.fields)?;
+        eprintln!("->> Finished writing {}", csv_path.display());
+    }
+
+    if let Some(dot_path) = &args.dot {
+        fs::write(dot_path, output.gateserver.to_dot())?;
+        eprintln!("->> Finished writing {}", dot_path.display());
+    }
+
+    if let Some(dir) = &args.protoc_bundle {
+        write_protoc_bundle(dir, &args.proto_message_name, &proto, &output.gateserver)?;
+        eprintln!("->> Finished writing protoc bundle to {}", dir.display());
+    }
+
+    if let Some(rust_path) = &args.rust_module {
+        let module =
+            render_rust_module(&output.gateserver, &output.fields, &args.proto_message_name);
+        fs::write(rust_path, module)?;
+        eprintln!("->> Finished writing {}", rust_path.display());
+    }
+
+    if let Some(dir) = &args.save_proto_dir {
+        match save_proto_snapshot(dir, game_version, &proto)? {
+            Some(path) => eprintln!("->> Saved proto snapshot to {}", path.display()),
+            None => eprintln!("->> Proto unchanged since the last snapshot"),
+        }
+    }
+
+    if let Some(history_path) = &args.history {
+        let entry = HistoryEntry::new(game_version, &output.hotfix, &output.gateserver);
+        append_history(history_path, &entry)?;
+    }
+
+    eprintln!("->> Summary: {}", output.summary());
+    eprintln!("->> Elapsed time: {}s", start_time.elapsed().as_secs_f32());
+
+    if let Some((previous_path, previous)) = previous {
+        let changes = output.hotfix.diff(&previous);
+
+        if changes.is_empty() {
+            eprintln!("->> No changes since {}", previous_path.display());
+        } else {
+            for change in &changes {
+                println!("{}: {} -> {}", change.field, change.old, change.new);
+            }
+            return Ok(Some(EXIT_DIFFERENT));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Writes the game version and the branch of the game installed in `path` on their own lines, for the `version` command.
+fn print_version(
+    out: &mut impl Write,
+    args: &Args,
+    path: &std::path::Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let binary_version_path = get_binary_version_path(path, args.assets_subpath());
+    let binary_version_buffer =
+        read_capped(&binary_version_path, args.max_file_size).map_err(|source| AppError::File {
+            path: binary_version_path,
+            source,
+        })?;
+    let binary_version = BinaryVersionData::try_from(binary_version_buffer)?;
+
+    let game_version = binary_version
+        .get_server_pak_type_version()
+        .ok_or(AppError::MissingGameVersion)?;
+
+    writeln!(out, "{game_version}")?;
+    writeln!(out, "{}", binary_version.branch)?;
+    Ok(())
+}
+
+/// Writes every top-level field of `gateserver` as a JSON object on its own line, for `--raw-output`.
+fn write_raw_output(out: &mut impl Write, gateserver: &DecodingResult) -> std::io::Result<()> {
+    for field in &gateserver.fields {
+        writeln!(out, "{}", field.to_json())?;
+    }
+    Ok(())
+}
+
+/// Writes the URL of the resource `kind` on its own line, for `--only`. Fails if the hotfix doesn't have it.
+fn write_only_url(
+    out: &mut impl Write,
+    hotfix: &Hotfix,
+    kind: ResourceKind,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let url = hotfix.url(kind);
+    if url.is_empty() {
+        return Err(AppError::MissingFields(kind.field_name().to_string()).into());
+    }
+    writeln!(out, "{url}")?;
+    Ok(())
+}
+
+/// Fetches the hotfix every `interval`, writing the output files whenever the gateserver message changes,
+/// until interrupted with Ctrl-C. Failed fetches are logged and retried on the next tick.
+async fn watch(
+    args: &Args,
+    game: &GameFiles,
+    interval: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut ticker = tokio::time::interval(interval);
+    let mut last_hash = None;
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = async {
+                ticker.tick().await;
+                poll_once(args, game, &mut last_hash).await
+            } => {}
+        }
+    }
+
+    eprintln!("->> Stopped watching");
+    Ok(())
+}
+
+/// Fetches the hotfix once, writing the output files if the gateserver message hash differs from `last_hash`,
+/// which is then updated unless writing failed. Returns whether the output was written.
+async fn poll_once(args: &Args, game: &GameFiles, last_hash: &mut Option<String>) -> bool {
+    match fetch_output(args, game).await {
+        Ok(Some(output)) => {
+            let hash = source_hash(&output.gateserver);
+            if last_hash.as_ref() == Some(&hash) {
+                log::info!("Gateserver unchanged ({hash})");
+                return false;
+            }
+
+            eprintln!("->> Gateserver changed ({hash})");
+            let written = match write_output(args, &output, &game.game_version, Instant::now()) {
+                Ok(None) => true,
+                Ok(Some(EXIT_DIFFERENT)) => {
+                    log::info!("Hotfix differs from the --diff file");
+                    true
+                }
+                // Nothing was written, e.g. during maintenance
+                Ok(Some(code)) => {
+                    log::info!("Output not written (exit code {code})");
+                    false
+                }
+                // Keep the previous hash so that the write is retried on the next tick
+                Err(e) => {
+                    log::warn!("Failed to write the output: {e}");
+                    return false;
+                }
+            };
+            *last_hash = Some(hash);
+            written
+        }
+        Ok(None) => {
+            log::warn!("Dispatch didn't list any region");
+            false
+        }
+        Err(e) => {
+            log::warn!("Fetch failed: {e}");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fetch_hotfix::decode::Decoder;
+    use std::path::Path;
+
+    #[test]
+    fn raw_output_writes_one_line_per_field() {
+        // Field 1 = 23301, field 2 = "127.0.0.1", field 3 = 1
+        let mut message = vec![0x08, 0x85, 0xB6, 0x01, 0x12, 9];
+        message.extend_from_slice(b"127.0.0.1");
+        message.extend_from_slice(&[0x18, 0x01]);
+        let gateserver = Decoder::new(message).decode().unwrap();
+
+        let mut out = Vec::new();
+        write_raw_output(&mut out, &gateserver).unwrap();
+
+        let lines = String::from_utf8(out).unwrap();
+        let lines = lines.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        for (line, field) in lines.iter().zip(&gateserver.fields) {
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(line).unwrap(),
+                field.to_json()
+            );
+        }
+    }
+
+    const LUA_URL: &str =
+        "https://autopatchcn.bhsr.com/lua/V2.2Live/output_7576543_0f1e2d3c4b5a/client";
+
+    const URLS: [&str; 4] = [
+        "https://autopatchcn.bhsr.com/asb/V2.2Live/output_7563532_5c6f0d11b0e5/client",
+        "https://autopatchcn.bhsr.com/design_data/V2.2Live/output_7581234_9a1b2c3d4e5f/client",
+        LUA_URL,
+        "https://autopatchcn.bhsr.com/ifix/V2.2Live/output_7570000_aabbccddeeff/client",
+    ];
+
+    /// Encodes a gateserver message holding `urls` and a port.
+    fn message_with_urls(urls: &[&str]) -> Vec<u8> {
+        let mut message = Vec::new();
+        for (number, url) in (1..).zip(urls) {
+            prost::encoding::string::encode(number, &url.to_string(), &mut message);
+        }
+        prost::encoding::uint32::encode(10, &23301, &mut message);
+        message
+    }
+
+    /// Decodes a gateserver message holding `urls` and a port.
+    fn output_with_urls(urls: &[&str]) -> FetchOutput {
+        decode_gateserver(message_with_urls(urls), Metadata::default()).unwrap()
+    }
+
+    /// Decodes a gateserver message holding every resource URL and a port.
+    fn sample_output() -> FetchOutput {
+        output_with_urls(&URLS)
+    }
+
+    fn parse(args: &[&str]) -> Args {
+        use clap::Parser as _;
+        Args::try_parse_from(std::iter::once("fetch-hotfix").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn diff_reads_the_previous_hotfix_before_overwriting_it() {
+        let out_dir = tempfile::tempdir().unwrap();
+        let previous_path = out_dir.path().join("hotfix-2.2.0.json");
+        let previous = Hotfix {
+            lua_url: String::from("https://autopatchcn.bhsr.com/lua/V2.1Live/output_1_a/client"),
+            ..sample_output().hotfix
+        };
+        fs::write(&previous_path, serde_json::to_string(&previous).unwrap()).unwrap();
+
+        let args = parse(&[
+            "--out-dir",
+            out_dir.path().to_str().unwrap(),
+            "--diff",
+            previous_path.to_str().unwrap(),
+        ]);
+        let code = write_output(&args, &sample_output(), "2.2.0", Instant::now()).unwrap();
+
+        assert_eq!(code, Some(EXIT_DIFFERENT));
+        let written: Hotfix =
+            serde_json::from_str(&fs::read_to_string(&previous_path).unwrap()).unwrap();
+        assert_eq!(written.lua_url, LUA_URL);
+    }
+
+    /// Game files pointing the dispatch query at `dispatch_url`.
+    fn game_files(dispatch_url: &str) -> GameFiles {
+        GameFiles {
+            client_config: ClientStartupConfig {
+                channel_name: String::from("CNPRODWin"),
+                bundle_identifier: String::new(),
+                product_name: String::from("StarRail"),
+                script_defines: String::new(),
+                global_dispatch_url_list: vec![dispatch_url.to_string()],
+            },
+            binary_version: BinaryVersionData {
+                branch: String::from("CNPRODWin2.2.0"),
+                revision: 7563532,
+                major_version: 2,
+                minor_version: 2,
+                patch_version: 0,
+                unknown: vec![0; 4 * 15],
+                time: String::new(),
+                pak_type: String::new(),
+                pak_type_detail: String::new(),
+                start_asset: String::new(),
+                start_design_data: String::new(),
+                dispatch_seed: String::from("seed"),
+                version_string: String::from("7563532-CNPRODWin2.2.0"),
+                version_hash: String::new(),
+                game_core_version: 0,
+                is_enable_exclude_asset: false,
+                sdk_ps_client_id: String::new(),
+            },
+            game_version: String::from("CNPRODWin2.2.0"),
+        }
+    }
+
+    #[tokio::test]
+    async fn deadline_aborts_a_hung_dispatch() {
+        // Connections are accepted by the OS but never answered
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let dispatch_url = format!("http://{}/query_dispatch", listener.local_addr().unwrap());
+
+        let start = Instant::now();
+        let result = fetch_output(&parse(&["--deadline", "1"]), &game_files(&dispatch_url)).await;
+
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<AppError>(),
+            Some(AppError::Timeout)
+        ));
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn missing_urls_fail_unless_partial_output_is_allowed() {
+        let partial = output_with_urls(&URLS[..3]);
+        let out_dir = tempfile::tempdir().unwrap();
+        let out_dir = out_dir.path().to_str().unwrap();
+        let hotfix_path = std::path::Path::new(out_dir).join("hotfix-2.2.0.json");
+
+        let result = write_output(
+            &parse(&["--out-dir", out_dir]),
+            &partial,
+            "2.2.0",
+            Instant::now(),
+        );
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<AppError>(),
+            Some(AppError::MissingFields(names)) if names == "ifix_url"
+        ));
+        assert!(!hotfix_path.exists());
+
+        for flag in ["--keep-going", "--no-need-url"] {
+            let args = parse(&["--out-dir", out_dir, flag]);
+            assert_eq!(
+                write_output(&args, &partial, "2.2.0", Instant::now()).unwrap(),
+                None
+            );
+
+            let written: Hotfix =
+                serde_json::from_str(&fs::read_to_string(&hotfix_path).unwrap()).unwrap();
+            assert_eq!(written.lua_url, LUA_URL);
+            assert!(written.ifix_url.is_empty());
+            fs::remove_file(&hotfix_path).unwrap();
         }
+    }
+
+    #[test]
+    fn out_dir_is_created_and_holds_the_outputs() {
+        let root = tempfile::tempdir().unwrap();
+        let out_dir = root.path().join("outputs").join("2.2.0");
 
-        let query_gateway_base = &dispatch_decoded_message.region_list[0].dispatch_url;
+        write_output(
+            &parse(&["--out-dir", out_dir.to_str().unwrap()]),
+            &sample_output(),
+            "2.2.0",
+            Instant::now(),
+        )
+        .unwrap();
 
-        let query_gateway_url = format!(
-            "{}?version={}&platform_type=1&language_type=3&dispatch_seed={}&channel_id=1&sub_channel_id=1&is_need_url=1",
-            query_gateway_base, game_version, binary_version.dispatch_seed
+        let mut written = fs::read_dir(&out_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        written.sort();
+        assert_eq!(
+            written,
+            [
+                "Gateserver-2.2.0.proto",
+                "hotfix-2.2.0.json",
+                "hotfix-2.2.0.meta.json"
+            ]
         );
+    }
 
-        println!("->> Gateway URL: {}", query_gateway_url);
+    #[test]
+    fn decodes_a_gateway_response_without_the_game_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gateway.b64");
+        let response = fetch_hotfix::base64::encode(&message_with_urls(&URLS));
+        fs::write(&path, format!("{response}\n")).unwrap();
 
-        let query_gateway_response = client.get(&query_gateway_url).send().await?.text().await?;
+        let output = decode_input(&parse(&["--gateway-file", path.to_str().unwrap()]))
+            .unwrap()
+            .unwrap();
 
-        let gateserver_decoded_base64 = rbase64::decode(&query_gateway_response)?;
+        assert_eq!(output.hotfix.lua_url, LUA_URL);
+        assert!(decode_input(&parse(&[])).unwrap().is_none());
+    }
 
-        let mut decoder = Decoder::new(gateserver_decoded_base64);
+    #[test]
+    fn require_fields_fails_on_absent_field_numbers() {
+        let out_dir = tempfile::tempdir().unwrap();
+        let out_dir = out_dir.path().to_str().unwrap();
+
+        let args = parse(&["--out-dir", out_dir, "--require-fields", "1,7,9"]);
+        let result = write_output(&args, &sample_output(), "2.2.0", Instant::now());
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<AppError>(),
+            Some(AppError::MissingFields(numbers)) if numbers == "#7, #9"
+        ));
+
+        let args = parse(&["--out-dir", out_dir, "--require-fields", "1,4,10"]);
+        assert!(write_output(&args, &sample_output(), "2.2.0", Instant::now()).is_ok());
+    }
 
-        let gateserver_decoded_message = decoder.decode()?;
+    #[test]
+    fn only_prints_the_requested_url() {
+        let hotfix = sample_output().hotfix;
 
-        let (hotfix_json, gateserver_proto) = Hotfix::create_from_simple_message(
-            gateserver_decoded_message,
-            dispatch_decoded_message,
+        let mut out = Vec::new();
+        write_only_url(&mut out, &hotfix, ResourceKind::Lua).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), format!("{LUA_URL}\n"));
+
+        let partial = output_with_urls(&URLS[..3]).hotfix;
+        let mut out = Vec::new();
+        let error = write_only_url(&mut out, &partial, ResourceKind::Ifix).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<AppError>(),
+            Some(AppError::MissingFields(name)) if name == "ifix_url"
+        ));
+        assert!(out.is_empty());
+    }
+
+    /// Encodes `s` the way BinaryVersion strings are stored: a flag byte, a varint length, then the bytes.
+    fn binary_string(s: &str) -> Vec<u8> {
+        let mut buf = vec![1, s.len() as u8];
+        buf.extend_from_slice(s.as_bytes());
+        buf
+    }
+
+    /// Writes a BinaryVersion file of the `branch` build with `version_string` into a new game folder.
+    fn game_folder(branch: &str, version_string: &str) -> tempfile::TempDir {
+        let mut buf = binary_string(branch);
+        for value in [7563532_u32, 2, 2, 0] {
+            buf.extend_from_slice(&value.to_be_bytes());
+        }
+        buf.extend_from_slice(&[0; 4 * 15]);
+        for s in ["", "", "", "", "", "seed", version_string, ""] {
+            buf.extend(binary_string(s));
+        }
+        buf.extend_from_slice(&0_u32.to_be_bytes());
+        buf.push(0);
+        buf.extend(binary_string(""));
+
+        let game = tempfile::tempdir().unwrap();
+        let assets = game.path().join("StarRail_Data/StreamingAssets");
+        fs::create_dir_all(&assets).unwrap();
+        fs::write(assets.join("BinaryVersion.bytes"), buf).unwrap();
+        game
+    }
+
+    #[test]
+    fn version_prints_the_game_version_and_branch() {
+        let game = game_folder("CNPRODWin2.2.0", "7563532-CNPRODWin2.2.0-x.y");
+
+        let mut out = Vec::new();
+        print_version(&mut out, &parse(&[]), game.path()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "CNPRODWin2.2.0\nCNPRODWin2.2.0\n"
         );
+    }
+
+    #[test]
+    fn version_fails_without_a_game_version() {
+        let game = game_folder("CNPRODWin2.2.0", "7563532");
 
-        let pretty_json = serde_json::to_string_pretty(&hotfix_json)?;
+        let error = print_version(&mut Vec::new(), &parse(&[]), game.path()).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<AppError>(),
+            Some(AppError::MissingGameVersion)
+        ));
 
-        let output_path = PathBuf::from(format!("hotfix-{}.json", game_version));
+        let error =
+            print_version(&mut Vec::new(), &parse(&[]), Path::new("/nonexistent")).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<AppError>(),
+            Some(AppError::File { .. })
+        ));
+    }
+
+    /// A minimal HTTP server answering every request with the current `body`, returning its base URL.
+    fn serve_body(body: std::sync::Arc<std::sync::Mutex<String>>) -> String {
+        use std::io::{BufRead, BufReader};
 
-        let mut file = fs::File::create(output_path)?;
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let body = body.lock().unwrap().clone();
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+            }
+        });
+        url
+    }
 
-        file.write_all(pretty_json.as_bytes())?;
+    /// Game files whose dispatch response, stored in `dir`, points to a gateway answering with `gateway_body`.
+    fn watched_game(
+        dir: &Path,
+        gateway_body: std::sync::Arc<std::sync::Mutex<String>>,
+    ) -> GameFiles {
+        game_files(&mock_dispatch(dir, gateway_body))
+    }
 
-        println!("->> Finished writing hotfix.json");
+    /// Stores in `dir` a dispatch response pointing to a gateway answering with `gateway_body`,
+    /// and returns its `file://` URL.
+    fn mock_dispatch(dir: &Path, gateway_body: std::sync::Arc<std::sync::Mutex<String>>) -> String {
+        use fetch_hotfix::proto::{Dispatch, RegionInfo};
+        use prost::Message as _;
 
-        let output_path = PathBuf::from(format!("Gateserver-{}.proto", game_version));
-        
-        let mut file = fs::File::create(output_path)?;
-        
-        file.write_all(gateserver_proto.as_bytes())?;
-        
-        println!("->> Finished writing Gateserver.proto");
+        let gateway_url = format!("{}/query_gateway", serve_body(gateway_body));
+        let dispatch_path = dir.join("dispatch.b64");
+        let dispatch = Dispatch {
+            region_list: vec![RegionInfo {
+                name: String::from("prod_gf_cn"),
+                dispatch_url: gateway_url,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        fs::write(
+            &dispatch_path,
+            fetch_hotfix::base64::encode(&dispatch.encode_to_vec()),
+        )
+        .unwrap();
+        reqwest::Url::from_file_path(&dispatch_path)
+            .unwrap()
+            .to_string()
+    }
 
-        println!("->> Elapsed time: {}s", start_time.elapsed().as_secs_f32());
+    #[tokio::test]
+    async fn watch_writes_the_output_only_when_the_gateserver_changes() {
+        let gateway_body = std::sync::Arc::new(std::sync::Mutex::new(
+            fetch_hotfix::base64::encode(&message_with_urls(&URLS)),
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let game = watched_game(dir.path(), gateway_body.clone());
 
-        Ok(())
-    } else {
-        println!("->> No folder selected.");
+        let out_dir = dir.path().join("out");
+        let args = parse(&["--out-dir", out_dir.to_str().unwrap()]);
+        let hotfix_path = out_dir.join("hotfix-CNPRODWin2.2.0.json");
+        let written_lua_url = || {
+            serde_json::from_str::<Hotfix>(&fs::read_to_string(&hotfix_path).unwrap())
+                .unwrap()
+                .lua_url
+        };
+        let mut last_hash = None;
 
-        Ok(())
+        assert!(poll_once(&args, &game, &mut last_hash).await);
+        assert_eq!(written_lua_url(), LUA_URL);
+
+        // Same gateserver message, nothing is written
+        fs::remove_file(&hotfix_path).unwrap();
+        assert!(!poll_once(&args, &game, &mut last_hash).await);
+        assert!(!hotfix_path.exists());
+
+        let new_lua_url =
+            "https://autopatchcn.bhsr.com/lua/V2.2Live/output_7590000_123456abcdef/client";
+        let mut urls = URLS;
+        urls[2] = new_lua_url;
+        *gateway_body.lock().unwrap() = fetch_hotfix::base64::encode(&message_with_urls(&urls));
+        assert!(poll_once(&args, &game, &mut last_hash).await);
+        assert_eq!(written_lua_url(), new_lua_url);
+    }
+
+    #[tokio::test]
+    async fn watch_retries_a_failed_write() {
+        let gateway_body = std::sync::Arc::new(std::sync::Mutex::new(
+            fetch_hotfix::base64::encode(&message_with_urls(&URLS)),
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let game = watched_game(dir.path(), gateway_body);
+
+        // A file in place of the output directory makes the write fail
+        let out_dir = dir.path().join("out");
+        fs::write(&out_dir, "").unwrap();
+        let args = parse(&["--out-dir", out_dir.to_str().unwrap()]);
+        let mut last_hash = None;
+
+        assert!(!poll_once(&args, &game, &mut last_hash).await);
+        assert_eq!(last_hash, None);
+
+        // The gateserver message is unchanged, but the write is retried
+        fs::remove_file(&out_dir).unwrap();
+        assert!(poll_once(&args, &game, &mut last_hash).await);
+        assert!(out_dir.join("hotfix-CNPRODWin2.2.0.json").exists());
+        assert!(last_hash.is_some());
+    }
+
+    #[test]
+    fn decodes_a_hex_input_without_the_game_files() {
+        let hex = message_with_urls(&URLS)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+
+        let output = decode_input(&parse(&["--hex-input", &hex]))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(output.hotfix.lua_url, LUA_URL);
+    }
+    /// Writes a ClientConfig file without dispatch URLs into the game folder `game`, to be used with `--dispatch-url`.
+    fn write_client_config(game: &Path) {
+        let mut buf = Vec::new();
+        for _ in 0..4 {
+            buf.extend(binary_string(""));
+        }
+        buf.extend_from_slice(&[0, 0, 0, 0]);
+        fs::write(
+            game.join("StarRail_Data/StreamingAssets/ClientConfig.bytes"),
+            buf,
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn batch_skips_the_versions_completed_by_an_earlier_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let gateway_body = std::sync::Arc::new(std::sync::Mutex::new(
+            fetch_hotfix::base64::encode(&message_with_urls(&URLS)),
+        ));
+        let dispatch_url = mock_dispatch(dir.path(), gateway_body);
+        let games = [
+            game_folder("CNPRODWin2.2.0", "7563532-CNPRODWin2.2.0"),
+            game_folder("CNPRODWin2.3.0", "7563532-CNPRODWin2.3.0"),
+        ];
+        for game in &games {
+            write_client_config(game.path());
+        }
+
+        let out_dir = dir.path().join("out");
+        let state_path = dir.path().join("batch.json");
+        let mut args = vec![
+            "--out-dir",
+            out_dir.to_str().unwrap(),
+            "--batch-state",
+            state_path.to_str().unwrap(),
+            "--dispatch-url",
+            dispatch_url.as_str(),
+        ];
+        for game in &games {
+            args.extend(["--batch", game.path().to_str().unwrap()]);
+        }
+
+        assert_eq!(run_batch(&parse(&args)).await.unwrap(), 2);
+        assert!(out_dir.join("hotfix-CNPRODWin2.2.0.json").exists());
+        assert!(out_dir.join("hotfix-CNPRODWin2.3.0.json").exists());
+
+        // Both versions were completed, the second run fetches nothing
+        fs::remove_dir_all(&out_dir).unwrap();
+        assert_eq!(run_batch(&parse(&args)).await.unwrap(), 0);
+        assert!(!out_dir.exists());
+
+        args.push("--force");
+        assert_eq!(run_batch(&parse(&args)).await.unwrap(), 2);
+        assert!(out_dir.join("hotfix-CNPRODWin2.3.0.json").exists());
     }
 }
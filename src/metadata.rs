@@ -0,0 +1,101 @@
+use serde::Serialize;
+
+/// Describes where a hotfix came from, so a saved result is self-describing.
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct Metadata {
+    /// Full dispatch URL that was queried, including the query string.
+    pub dispatch_url: String,
+    /// Name of the region whose gateway was queried.
+    pub region_name: String,
+    /// Dispatch seed sent with the gateway query.
+    pub dispatch_seed: String,
+    /// Server group the dispatch belongs to, if it could be told from its URL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region_tag: Option<RegionTag>,
+}
+
+/// The server groups a client build can be connected to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegionTag {
+    /// Mainland China servers.
+    Cn,
+    /// Overseas servers.
+    Global,
+    /// Beta test servers.
+    Beta,
+}
+
+/// Guesses the server group of a dispatch from the host of its URL, e.g. `globaldp-prod-os01.starrails.com` is global.
+/// Returns `None` for hosts that don't follow a known naming scheme.
+pub fn detect_region_tag(dispatch_url: &str) -> Option<RegionTag> {
+    let url = reqwest::Url::parse(dispatch_url).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+
+    if host.contains("beta") {
+        Some(RegionTag::Beta)
+    } else if host.ends_with(".bhsr.com") || host.contains("-cn") {
+        Some(RegionTag::Cn)
+    } else if host.ends_with(".starrails.com") || host.contains("-os") {
+        Some(RegionTag::Global)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_the_region_of_known_dispatch_hosts() {
+        for (url, tag) in [
+            (
+                "https://globaldp-prod-cn01.bhsr.com/query_dispatch",
+                RegionTag::Cn,
+            ),
+            (
+                "https://globaldp-prod-os01.starrails.com/query_dispatch?version=2.2.0",
+                RegionTag::Global,
+            ),
+            (
+                "https://globaldp-beta-cn01.bhsr.com/query_dispatch",
+                RegionTag::Beta,
+            ),
+            (
+                "http://dispatch-cn.example.com/query_dispatch",
+                RegionTag::Cn,
+            ),
+        ] {
+            assert_eq!(detect_region_tag(url), Some(tag), "{url}");
+        }
+    }
+
+    #[test]
+    fn leaves_unknown_dispatch_hosts_untagged() {
+        assert_eq!(
+            detect_region_tag("http://127.0.0.1:21000/query_dispatch"),
+            None
+        );
+        assert_eq!(detect_region_tag("not a url"), None);
+    }
+
+    #[test]
+    fn serializes_the_region_tag_in_lowercase() {
+        let metadata = Metadata {
+            region_tag: Some(RegionTag::Global),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(&metadata).unwrap()["region_tag"],
+            "global"
+        );
+        assert!(
+            serde_json::to_value(Metadata::default())
+                .unwrap()
+                .get("region_tag")
+                .is_none()
+        );
+    }
+}
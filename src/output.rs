@@ -0,0 +1,581 @@
+use crate::{
+    decode::DecodingResult,
+    hotfix::{Hotfix, InferredField},
+    proto::{Dispatch, RegionInfo},
+    resource::ResourceKind,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Saves `proto` as the next snapshot in `<dir>/<version>/`, named `Gateserver-<n>.proto` with an increasing `n`.
+/// Nothing is written if the proto is identical to the latest snapshot for that version.
+/// Returns the path of the written snapshot, if any.
+pub fn save_proto_snapshot(dir: &Path, version: &str, proto: &str) -> io::Result<Option<PathBuf>> {
+    let version_dir = dir.join(version);
+    fs::create_dir_all(&version_dir)?;
+
+    let latest = fs::read_dir(&version_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("Gateserver-")?
+                .strip_suffix(".proto")?
+                .parse::<u32>()
+                .ok()
+        })
+        .max();
+
+    if let Some(latest) = latest {
+        let latest_path = version_dir.join(snapshot_name(latest));
+        if fs::read_to_string(latest_path)? == proto {
+            return Ok(None);
+        }
+    }
+
+    let path = version_dir.join(snapshot_name(latest.map_or(1, |n| n + 1)));
+    fs::write(&path, proto)?;
+    Ok(Some(path))
+}
+
+fn snapshot_name(index: u32) -> String {
+    format!("Gateserver-{index:03}.proto")
+}
+
+/// Writes one CSV row per top-level field of `result`, with its number, wire type,
+/// inferred name (empty if unknown) and a preview of its value.
+pub fn write_fields_csv(
+    path: &Path,
+    result: &DecodingResult,
+    inferred: &[InferredField],
+) -> io::Result<()> {
+    let mut csv = String::from("field,wire_type,name,value\n");
+
+    for field in &result.fields {
+        let name = inferred
+            .iter()
+            .find(|inferred| inferred.number == field.field)
+            .map_or("", |inferred| inferred.name.as_str());
+
+        csv += &format!(
+            "{},{},{},{}\n",
+            field.field,
+            field.wire_type.as_str(),
+            csv_escape(name),
+            csv_escape(&field.value.preview(64))
+        );
+    }
+
+    fs::write(path, csv)
+}
+
+/// Quotes a CSV cell if it contains a separator, quote or line break.
+fn csv_escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/// Writes `<message_name>.proto` and the re-encoded message as `<message_name>.bin` into `dir`, so that
+/// `protoc --decode=<message_name> <message_name>.proto < <message_name>.bin` can be run on them.
+pub fn write_protoc_bundle(
+    dir: &Path,
+    message_name: &str,
+    proto: &str,
+    result: &DecodingResult,
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(format!("{message_name}.proto")), proto)?;
+    fs::write(dir.join(format!("{message_name}.bin")), result.encode())
+}
+
+/// Wire type counts of the gateserver message by game version, as stored in a baseline file.
+type WireBaselines = BTreeMap<String, BTreeMap<String, usize>>;
+
+/// Compares the wire type distribution of `result` against the baseline recorded for `version` in the JSON file at `path`.
+/// Returns a description of every wire type whose share of the fields moved by more than `threshold` (0 to 1).
+/// If the file has no baseline for `version` yet, the current distribution is recorded as the baseline instead.
+pub fn check_wire_baseline(
+    path: &Path,
+    version: &str,
+    result: &DecodingResult,
+    threshold: f64,
+) -> io::Result<Vec<String>> {
+    let mut baselines: WireBaselines = match fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => WireBaselines::new(),
+        Err(e) => return Err(e),
+    };
+
+    let current: BTreeMap<String, usize> = result
+        .wire_type_counts()
+        .into_iter()
+        .map(|(wire_type, count)| (wire_type.to_string(), count))
+        .collect();
+
+    let Some(baseline) = baselines.get(version) else {
+        baselines.insert(version.to_string(), current);
+        fs::write(path, serde_json::to_string_pretty(&baselines)?)?;
+        return Ok(Vec::new());
+    };
+
+    let share = |counts: &BTreeMap<String, usize>, wire_type: &str| {
+        let total = counts.values().sum::<usize>().max(1);
+        counts.get(wire_type).copied().unwrap_or_default() as f64 / total as f64
+    };
+
+    Ok(baseline
+        .keys()
+        .chain(current.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|wire_type| {
+            let (expected, actual) = (share(baseline, wire_type), share(&current, wire_type));
+            ((expected - actual).abs() > threshold).then(|| {
+                format!(
+                    "{wire_type} fields make up {:.0}% of the message, {:.0}% in the baseline",
+                    actual * 100.0,
+                    expected * 100.0
+                )
+            })
+        })
+        .collect())
+}
+
+/// A file format the hotfix can be written in.
+pub trait OutputFormat {
+    /// Extension of the written file, without the leading dot.
+    fn extension(&self) -> &'static str;
+    /// Renders `hotfix` in this format.
+    fn render(&self, hotfix: &Hotfix) -> io::Result<String>;
+}
+
+/// The JSON output, minified if `compact` is set and pretty-printed otherwise.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json {
+    pub compact: bool,
+}
+
+impl OutputFormat for Json {
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+
+    fn render(&self, hotfix: &Hotfix) -> io::Result<String> {
+        Ok(if self.compact {
+            serde_json::to_string(hotfix)?
+        } else {
+            serde_json::to_string_pretty(hotfix)?
+        })
+    }
+}
+
+/// One `key=value` line per field, named like the JSON fields, as read by server emulators.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EmulatorConfig;
+
+impl OutputFormat for EmulatorConfig {
+    fn extension(&self) -> &'static str {
+        "cfg"
+    }
+
+    fn render(&self, hotfix: &Hotfix) -> io::Result<String> {
+        Ok(hotfix
+            .entries()
+            .into_iter()
+            .map(|(key, value)| format!("{key}={value}\n"))
+            .collect())
+    }
+}
+
+/// One line of the `--history` file. Fields are only ever added to keep older lines readable.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct HistoryEntry {
+    /// Seconds since the Unix epoch at which the run finished.
+    pub timestamp: u64,
+    /// Game version the hotfix was fetched for.
+    pub version: String,
+    /// FNV-1a hash of the gateserver message, as hex, to spot changes that don't affect the URLs.
+    pub source_hash: String,
+    /// The resource URLs, by field name.
+    pub urls: BTreeMap<&'static str, String>,
+}
+
+impl HistoryEntry {
+    /// Creates the entry of a run that fetched `hotfix` from the `gateserver` message, timestamped now.
+    pub fn new(version: &str, hotfix: &Hotfix, gateserver: &DecodingResult) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            version: version.to_string(),
+            source_hash: source_hash(gateserver),
+            urls: ResourceKind::ALL
+                .into_iter()
+                .map(|kind| (kind.field_name(), hotfix.url(kind).to_string()))
+                .collect(),
+        }
+    }
+}
+
+/// Appends `entry` as a JSON line to the history file at `path`, creating it if needed.
+/// The line is written with a single `write` call on a file opened in append mode, which places it at the end
+/// of the file in one piece, so lines of concurrent runs don't interleave on local filesystems (NFS gives no such
+/// guarantee). A short write isn't completed with a second call, which could interleave, but fails with
+/// `io::ErrorKind::WriteZero`, leaving a truncated line behind.
+pub fn append_history(path: &Path, entry: &HistoryEntry) -> io::Result<()> {
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+
+    let written = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write(line.as_bytes())?;
+    if written != line.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::WriteZero,
+            format!(
+                "only {written} of {} bytes were appended to {}",
+                line.len(),
+                path.display()
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Versions completed by a batch run, recorded in a JSON file so that an interrupted run can be resumed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BatchState {
+    /// Game versions whose output was written.
+    pub completed: BTreeSet<String>,
+}
+
+impl BatchState {
+    /// Reads the state file at `path`, or returns an empty state if the file doesn't exist yet.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns whether the output of `version` was written by an earlier run.
+    pub fn is_completed(&self, version: &str) -> bool {
+        self.completed.contains(version)
+    }
+
+    /// Records `version` as completed and rewrites the state file at `path`, so that progress survives a later failure.
+    pub fn mark_completed(&mut self, path: &Path, version: &str) -> io::Result<()> {
+        self.completed.insert(version.to_string());
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Serializable view of a decoded dispatch response, written by `--save-dispatch`.
+/// Kept apart from `Dispatch` so that the prost-generated types stay untouched.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DispatchJson<'a> {
+    pub retcode: u32,
+    pub msg: &'a str,
+    pub top_sever_region_name: &'a str,
+    pub region_list: Vec<RegionJson<'a>>,
+    pub stop_desc: &'a str,
+}
+
+/// Serializable view of a `RegionInfo`, see `DispatchJson`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RegionJson<'a> {
+    pub name: &'a str,
+    pub title: &'a str,
+    pub dispatch_url: &'a str,
+    pub env_type: &'a str,
+    pub display_name: &'a str,
+    pub msg: &'a str,
+}
+
+impl<'a> From<&'a Dispatch> for DispatchJson<'a> {
+    fn from(dispatch: &'a Dispatch) -> Self {
+        Self {
+            retcode: dispatch.retcode,
+            msg: &dispatch.msg,
+            top_sever_region_name: &dispatch.top_sever_region_name,
+            region_list: dispatch.region_list.iter().map(RegionJson::from).collect(),
+            stop_desc: &dispatch.stop_desc,
+        }
+    }
+}
+
+impl<'a> From<&'a RegionInfo> for RegionJson<'a> {
+    fn from(region: &'a RegionInfo) -> Self {
+        Self {
+            name: &region.name,
+            title: &region.title,
+            dispatch_url: &region.dispatch_url,
+            env_type: &region.env_type,
+            display_name: &region.display_name,
+            msg: &region.msg,
+        }
+    }
+}
+
+/// Returns the FNV-1a hash of the `gateserver` message, as hex.
+pub fn source_hash(gateserver: &DecodingResult) -> String {
+    format!("{:016x}", fnv1a(&gateserver.encode()))
+}
+
+/// 64-bit FNV-1a, used because its output stays the same across Rust versions unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        decode::Decoder,
+        hotfix::render_proto,
+        resource::UrlPatterns,
+        test_util::{REGION_NAME, gateserver_message, push_len_field, push_varint_field},
+    };
+
+    #[test]
+    fn saves_identical_protos_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let proto = "syntax = \"proto3\";\n\nmessage Gateserver {\n\tstring lua_url = 6;\n}";
+
+        let first = save_proto_snapshot(dir.path(), "2.2.0", proto).unwrap();
+        let second = save_proto_snapshot(dir.path(), "2.2.0", proto).unwrap();
+
+        assert_eq!(first, Some(dir.path().join("2.2.0/Gateserver-001.proto")));
+        assert_eq!(second, None);
+        assert_eq!(fs::read_dir(dir.path().join("2.2.0")).unwrap().count(), 1);
+
+        let changed = save_proto_snapshot(dir.path(), "2.2.0", &proto.replace('6', "7")).unwrap();
+        assert_eq!(changed, Some(dir.path().join("2.2.0/Gateserver-002.proto")));
+    }
+
+    /// Decodes `gateserver_message` and infers its fields.
+    fn sample() -> (DecodingResult, Hotfix, Vec<InferredField>) {
+        let gateserver = Decoder::new(gateserver_message()).decode().unwrap();
+        let (hotfix, fields) =
+            Hotfix::infer_fields(&gateserver, REGION_NAME, &UrlPatterns::default());
+        (gateserver, hotfix, fields)
+    }
+
+    #[test]
+    fn writes_the_fields_as_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fields.csv");
+        let (gateserver, _, fields) = sample();
+
+        write_fields_csv(&path, &gateserver, &fields).unwrap();
+
+        let csv = fs::read_to_string(path).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "field,wire_type,name,value");
+        assert_eq!(lines[3], "3,varint,port,23301");
+        assert_eq!(lines.len(), gateserver.fields.len() + 1);
+    }
+
+    #[test]
+    fn writes_a_matching_proto_and_bin_pair() {
+        let dir = tempfile::tempdir().unwrap();
+        let (gateserver, _, fields) = sample();
+        let proto = render_proto(&fields);
+
+        write_protoc_bundle(dir.path(), "Gateserver", &proto, &gateserver).unwrap();
+
+        let proto = fs::read_to_string(dir.path().join("Gateserver.proto")).unwrap();
+        let bin = fs::read(dir.path().join("Gateserver.bin")).unwrap();
+        let proto_numbers = proto
+            .lines()
+            .filter_map(|line| line.split(" = ").nth(1)?.strip_suffix(';')?.parse().ok())
+            .collect::<BTreeSet<u32>>();
+        let bin_numbers = Decoder::new(bin)
+            .decode()
+            .unwrap()
+            .fields
+            .iter()
+            .map(|field| field.field)
+            .collect::<BTreeSet<u32>>();
+
+        assert!(proto.contains("message Gateserver {"));
+        assert!(!proto_numbers.is_empty());
+        assert!(proto_numbers.is_subset(&bin_numbers));
+    }
+
+    /// Decodes a message of `len_fields` length-delimited fields followed by `varint_fields` varint fields.
+    fn with_wire_types(len_fields: u32, varint_fields: u32) -> DecodingResult {
+        let mut message = Vec::new();
+        for number in 1..=len_fields {
+            push_len_field(&mut message, number, b"text");
+        }
+        for number in 1..=varint_fields {
+            push_varint_field(&mut message, len_fields + number, 23301);
+        }
+        Decoder::new(message).decode().unwrap()
+    }
+
+    #[test]
+    fn warns_when_the_wire_types_drift_from_the_baseline() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("baselines.json");
+
+        let recorded = check_wire_baseline(&path, "2.2.0", &with_wire_types(2, 2), 0.25).unwrap();
+        assert!(recorded.is_empty());
+        assert!(path.is_file());
+
+        let close = check_wire_baseline(&path, "2.2.0", &with_wire_types(3, 3), 0.25).unwrap();
+        assert!(close.is_empty());
+
+        let skewed = check_wire_baseline(&path, "2.2.0", &with_wire_types(0, 4), 0.25).unwrap();
+        assert_eq!(
+            skewed,
+            [
+                "len fields make up 0% of the message, 50% in the baseline",
+                "varint fields make up 100% of the message, 50% in the baseline",
+            ]
+        );
+
+        // Other versions get their own baseline
+        let other = check_wire_baseline(&path, "2.3.0", &with_wire_types(0, 4), 0.25).unwrap();
+        assert!(other.is_empty());
+    }
+
+    #[test]
+    fn renders_the_emulator_config_format() {
+        let hotfix = Hotfix {
+            asset_bundle_url: String::from("https://example.com/asb/output_1_a/client"),
+            ex_resource_url: String::from("https://example.com/design_data/output_2_b/client"),
+            lua_url: String::from("https://example.com/lua/output_3_c/client"),
+            ifix_url: String::new(),
+            custom_mdk_res_version: 3,
+            custom_ifix_version: 0,
+            ports: vec![23301, 23302],
+            ..Default::default()
+        };
+
+        assert_eq!(EmulatorConfig.extension(), "cfg");
+        assert_eq!(
+            EmulatorConfig.render(&hotfix).unwrap(),
+            "asset_bundle_url=https://example.com/asb/output_1_a/client\n\
+             ex_resource_url=https://example.com/design_data/output_2_b/client\n\
+             lua_url=https://example.com/lua/output_3_c/client\n\
+             ifix_url=\n\
+             custom_mdk_res_version=3\n\
+             custom_ifix_version=0\n\
+             ports=23301,23302\n"
+        );
+    }
+
+    #[test]
+    fn serializes_the_dispatch_with_every_region() {
+        let dispatch = Dispatch {
+            retcode: 0,
+            msg: String::from("OK"),
+            top_sever_region_name: String::from(REGION_NAME),
+            region_list: vec![RegionInfo {
+                name: String::from(REGION_NAME),
+                title: String::from("China"),
+                dispatch_url: String::from("https://prod-gf-cn-dp01.bhsr.com/query_gateway"),
+                env_type: String::from("2"),
+                display_name: String::from("China"),
+                msg: String::from("OK"),
+            }],
+            stop_desc: String::new(),
+        };
+
+        assert_eq!(
+            serde_json::to_value(DispatchJson::from(&dispatch)).unwrap(),
+            serde_json::json!({
+                "retcode": 0,
+                "msg": "OK",
+                "top_sever_region_name": REGION_NAME,
+                "region_list": [{
+                    "name": REGION_NAME,
+                    "title": "China",
+                    "dispatch_url": "https://prod-gf-cn-dp01.bhsr.com/query_gateway",
+                    "env_type": "2",
+                    "display_name": "China",
+                    "msg": "OK",
+                }],
+                "stop_desc": "",
+            })
+        );
+    }
+
+    #[test]
+    fn each_run_appends_a_history_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let (gateserver, hotfix, _) = sample();
+
+        append_history(&path, &HistoryEntry::new("2.2.0", &hotfix, &gateserver)).unwrap();
+        append_history(&path, &HistoryEntry::new("2.3.0", &hotfix, &gateserver)).unwrap();
+
+        let history = fs::read_to_string(path).unwrap();
+        let lines = history
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["version"], "2.2.0");
+        assert_eq!(lines[1]["version"], "2.3.0");
+        assert_eq!(lines[0]["source_hash"], lines[1]["source_hash"]);
+        assert_eq!(lines[0]["urls"]["lua_url"], hotfix.lua_url);
+        assert!(lines[0]["timestamp"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn concurrent_runs_append_whole_history_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let (gateserver, hotfix, _) = sample();
+
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let (path, hotfix, gateserver) = (&path, &hotfix, &gateserver);
+                scope.spawn(move || {
+                    for j in 0..20 {
+                        let entry = HistoryEntry::new(&format!("2.{i}.{j}"), hotfix, gateserver);
+                        append_history(path, &entry).unwrap();
+                    }
+                });
+            }
+        });
+
+        let history = fs::read_to_string(path).unwrap();
+        assert_eq!(history.lines().count(), 160);
+        for line in history.lines() {
+            serde_json::from_str::<serde_json::Value>(line).unwrap();
+        }
+    }
+
+    #[test]
+    fn batch_state_survives_a_new_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("batch.json");
+
+        let mut state = BatchState::read(&path).unwrap();
+        assert_eq!(state, BatchState::default());
+        state.mark_completed(&path, "CNPRODWin2.2.0").unwrap();
+
+        let state = BatchState::read(&path).unwrap();
+        assert!(state.is_completed("CNPRODWin2.2.0"));
+        assert!(!state.is_completed("CNPRODWin2.3.0"));
+    }
+}
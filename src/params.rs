@@ -0,0 +1,265 @@
+use crate::{language::Language, platform::Platform};
+use std::{collections::HashMap, time::Duration};
+
+/// Field read by default from responses wrapped in a JSON object.
+pub const DEFAULT_ENVELOPE_FIELD: &str = "content";
+
+/// Query parameters sent along with the dispatch and gateway requests, and how the requests are made.
+/// Defaults match the values the official PC client sends.
+#[derive(Debug, Clone)]
+pub struct DispatchParams {
+    /// Game version, e.g. `3.0.0`.
+    pub version: String,
+    /// Language of the client.
+    pub language_type: u32,
+    /// Platform sent with the dispatch query.
+    pub platform_type: u32,
+    /// Platform sent with the gateway query.
+    pub gateway_platform_type: u32,
+    /// Distribution channel.
+    pub channel_id: u32,
+    /// Distribution sub-channel.
+    pub sub_channel_id: u32,
+    /// Whether the gateway should include the resource URLs in its response.
+    pub is_need_url: bool,
+    /// Channel identifiers used instead of the defaults when querying the gateway of a region, by region name.
+    pub region_channels: HashMap<String, ChannelOverride>,
+    /// Field holding the base64 payload when a response is wrapped in a JSON object.
+    pub envelope_field: String,
+    /// Time allowed for the dispatch request, or the client's default if `None`.
+    pub dispatch_timeout: Option<Duration>,
+    /// Time allowed for the gateway request, or the client's default if `None`.
+    pub gateway_timeout: Option<Duration>,
+}
+
+/// Channel identifiers overriding the defaults for a single region.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelOverride {
+    pub channel_id: Option<u32>,
+    pub sub_channel_id: Option<u32>,
+}
+
+impl Default for DispatchParams {
+    fn default() -> Self {
+        Self {
+            version: String::new(),
+            language_type: 3,
+            platform_type: 3,
+            gateway_platform_type: 1,
+            channel_id: 1,
+            sub_channel_id: 1,
+            is_need_url: true,
+            region_channels: HashMap::new(),
+            envelope_field: String::from(DEFAULT_ENVELOPE_FIELD),
+            dispatch_timeout: None,
+            gateway_timeout: None,
+        }
+    }
+}
+
+impl DispatchParams {
+    /// Creates parameters for the given game version with every other value set to its default.
+    pub fn new(version: impl Into<String>) -> Self {
+        Self::default().with_version(version)
+    }
+
+    /// Sets the game version, e.g. `3.0.0`.
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Sets the raw `language_type` code, see `with_language` for a known language.
+    pub fn with_language_type(mut self, language_type: u32) -> Self {
+        self.language_type = language_type;
+        self
+    }
+
+    /// Sets `language_type` from the given language.
+    pub fn with_language(self, language: Language) -> Self {
+        self.with_language_type(language.language_type())
+    }
+
+    /// Sets the `platform_type` sent with the dispatch query only.
+    pub fn with_platform_type(mut self, platform_type: u32) -> Self {
+        self.platform_type = platform_type;
+        self
+    }
+
+    /// Sets the dispatch and gateway `platform_type` from the given platform.
+    pub fn with_platform(self, platform: Platform) -> Self {
+        self.with_platform_type(platform.platform_type())
+            .with_gateway_platform_type(platform.gateway_platform_type())
+    }
+
+    /// Sets the `platform_type` sent with the gateway query only.
+    pub fn with_gateway_platform_type(mut self, platform_type: u32) -> Self {
+        self.gateway_platform_type = platform_type;
+        self
+    }
+
+    /// Sets the distribution channel sent with both queries.
+    pub fn with_channel_id(mut self, channel_id: u32) -> Self {
+        self.channel_id = channel_id;
+        self
+    }
+
+    /// Sets the distribution sub-channel sent with both queries.
+    pub fn with_sub_channel_id(mut self, sub_channel_id: u32) -> Self {
+        self.sub_channel_id = sub_channel_id;
+        self
+    }
+
+    /// Sets whether the gateway should include the resource URLs in its response.
+    pub fn with_is_need_url(mut self, is_need_url: bool) -> Self {
+        self.is_need_url = is_need_url;
+        self
+    }
+
+    /// Sets the channel identifiers used for the gateway of the given region.
+    pub fn with_region_channel(
+        mut self,
+        region_name: impl Into<String>,
+        channels: ChannelOverride,
+    ) -> Self {
+        self.region_channels.insert(region_name.into(), channels);
+        self
+    }
+
+    /// Sets the field holding the base64 payload when a response is wrapped in a JSON object.
+    pub fn with_envelope_field(mut self, envelope_field: impl Into<String>) -> Self {
+        self.envelope_field = envelope_field.into();
+        self
+    }
+
+    /// Sets the time allowed for the dispatch request.
+    pub fn with_dispatch_timeout(mut self, timeout: Duration) -> Self {
+        self.dispatch_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the time allowed for the gateway request.
+    pub fn with_gateway_timeout(mut self, timeout: Duration) -> Self {
+        self.gateway_timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the parameters to use for the gateway of the given region, with its channel overrides applied.
+    pub fn for_region(&self, region_name: &str) -> Self {
+        let mut params = self.clone();
+        if let Some(channels) = self.region_channels.get(region_name) {
+            params.channel_id = channels.channel_id.unwrap_or(self.channel_id);
+            params.sub_channel_id = channels.sub_channel_id.unwrap_or(self.sub_channel_id);
+        }
+        params
+    }
+
+    /// Builds the query string (without the leading `?`) for the global dispatch request.
+    pub fn dispatch_query(&self) -> String {
+        format!(
+            "version={}&language_type={}&platform_type={}&channel_id={}&sub_channel_id={}&is_new_format=1",
+            self.version,
+            self.language_type,
+            self.platform_type,
+            self.channel_id,
+            self.sub_channel_id
+        )
+    }
+
+    /// Builds the query string (without the leading `?`) for the region gateway request.
+    pub fn gateway_query(&self, dispatch_seed: &str) -> String {
+        format!(
+            "version={}&platform_type={}&language_type={}&dispatch_seed={}&channel_id={}&sub_channel_id={}&is_need_url={}",
+            self.version,
+            self.gateway_platform_type,
+            self.language_type,
+            dispatch_seed,
+            self.channel_id,
+            self.sub_channel_id,
+            self.is_need_url as u8
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispatch_query_uses_defaults() {
+        let params = DispatchParams::new("3.0.0");
+
+        assert_eq!(
+            params.dispatch_query(),
+            "version=3.0.0&language_type=3&platform_type=3&channel_id=1&sub_channel_id=1&is_new_format=1"
+        );
+    }
+
+    #[test]
+    fn gateway_query_uses_defaults() {
+        let params = DispatchParams::new("3.0.0");
+
+        assert_eq!(
+            params.gateway_query("seed"),
+            "version=3.0.0&platform_type=1&language_type=3&dispatch_seed=seed&channel_id=1&sub_channel_id=1&is_need_url=1"
+        );
+    }
+
+    #[test]
+    fn builder_overrides_appear_in_both_queries() {
+        let params = DispatchParams::new("3.0.0")
+            .with_language_type(1)
+            .with_platform_type(2)
+            .with_gateway_platform_type(4)
+            .with_channel_id(5)
+            .with_sub_channel_id(6);
+
+        assert_eq!(
+            params.dispatch_query(),
+            "version=3.0.0&language_type=1&platform_type=2&channel_id=5&sub_channel_id=6&is_new_format=1"
+        );
+        assert_eq!(
+            params.gateway_query("seed"),
+            "version=3.0.0&platform_type=4&language_type=1&dispatch_seed=seed&channel_id=5&sub_channel_id=6&is_need_url=1"
+        );
+    }
+
+    #[test]
+    fn gateway_query_toggles_is_need_url() {
+        let with_urls = DispatchParams::new("3.0.0").gateway_query("seed");
+        let without_urls = DispatchParams::new("3.0.0")
+            .with_is_need_url(false)
+            .gateway_query("seed");
+
+        assert!(with_urls.ends_with("&is_need_url=1"));
+        assert!(without_urls.ends_with("&is_need_url=0"));
+        assert_eq!(
+            with_urls.trim_end_matches("1"),
+            without_urls.trim_end_matches("0")
+        );
+    }
+
+    #[test]
+    fn region_channels_apply_to_their_region_only() {
+        let params = DispatchParams::new("3.0.0").with_region_channel(
+            "prod_official_usa",
+            ChannelOverride {
+                channel_id: Some(5),
+                sub_channel_id: None,
+            },
+        );
+
+        assert!(
+            params
+                .for_region("prod_official_usa")
+                .gateway_query("seed")
+                .ends_with("&channel_id=5&sub_channel_id=1&is_need_url=1")
+        );
+        assert!(
+            params
+                .for_region("prod_gf_cn")
+                .gateway_query("seed")
+                .ends_with("&channel_id=1&sub_channel_id=1&is_need_url=1")
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::{fmt, path::Path, str::FromStr};
+
+/// The platform a client build targets.
+/// Determines both the `platform_type` query parameter and where the streaming assets live.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub enum Platform {
+    #[default]
+    Pc,
+    Android,
+    Ios,
+    Ps,
+}
+
+impl Platform {
+    /// Value of the `platform_type` query parameter for this platform.
+    pub fn platform_type(self) -> u32 {
+        match self {
+            Platform::Ios => 1,
+            Platform::Android => 2,
+            Platform::Pc => 3,
+            Platform::Ps => 11,
+        }
+    }
+
+    /// Value of the `platform_type` query parameter of the gateway query for this platform.
+    /// The PC client sends 1 there rather than its dispatch value, the other platforms send the same value to both.
+    pub fn gateway_platform_type(self) -> u32 {
+        match self {
+            Platform::Pc => 1,
+            platform => platform.platform_type(),
+        }
+    }
+
+    /// Path of the streaming assets folder, relative to the game folder (or the extracted package root).
+    pub fn streaming_assets_path(self) -> &'static Path {
+        Path::new(match self {
+            Platform::Pc => "StarRail_Data/StreamingAssets",
+            Platform::Android => "assets",
+            Platform::Ios => "Data/Raw",
+            Platform::Ps => "Media/StreamingAssets",
+        })
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pc" | "windows" => Ok(Platform::Pc),
+            "android" => Ok(Platform::Android),
+            "ios" => Ok(Platform::Ios),
+            "ps" | "ps5" => Ok(Platform::Ps),
+            _ => Err(format!("unknown platform: {s}")),
+        }
+    }
+}
+
+impl TryFrom<String> for Platform {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Platform::Pc => "pc",
+            Platform::Android => "android",
+            Platform::Ios => "ios",
+            Platform::Ps => "ps",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_each_platform_to_its_params_and_path() {
+        let expected = [
+            (Platform::Pc, 3, 1, "StarRail_Data/StreamingAssets"),
+            (Platform::Android, 2, 2, "assets"),
+            (Platform::Ios, 1, 1, "Data/Raw"),
+            (Platform::Ps, 11, 11, "Media/StreamingAssets"),
+        ];
+
+        for (platform, platform_type, gateway_platform_type, path) in expected {
+            assert_eq!(platform.platform_type(), platform_type, "{platform}");
+            assert_eq!(
+                platform.gateway_platform_type(),
+                gateway_platform_type,
+                "{platform}"
+            );
+            assert_eq!(platform.streaming_assets_path(), Path::new(path));
+        }
+    }
+
+    #[test]
+    fn defaults_to_pc() {
+        assert_eq!(Platform::default(), Platform::Pc);
+    }
+
+    #[test]
+    fn parses_names_and_aliases() {
+        assert_eq!("Windows".parse(), Ok(Platform::Pc));
+        assert_eq!("android".parse(), Ok(Platform::Android));
+        assert_eq!("IOS".parse(), Ok(Platform::Ios));
+        assert_eq!("ps5".parse(), Ok(Platform::Ps));
+        assert!("switch".parse::<Platform>().is_err());
+    }
+}
@@ -0,0 +1,344 @@
+use std::str::FromStr;
+
+/// The kinds of resources the gateserver hands out URLs for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    AssetBundle,
+    ExResource,
+    Lua,
+    Ifix,
+}
+
+impl ResourceKind {
+    /// Every resource kind, in the order they are matched in.
+    pub const ALL: [ResourceKind; 4] = [
+        ResourceKind::AssetBundle,
+        ResourceKind::ExResource,
+        ResourceKind::Lua,
+        ResourceKind::Ifix,
+    ];
+
+    /// Name of the URL field for this resource, as written in the hotfix output.
+    pub fn field_name(self) -> &'static str {
+        match self {
+            ResourceKind::AssetBundle => "asset_bundle_url",
+            ResourceKind::ExResource => "ex_resource_url",
+            ResourceKind::Lua => "lua_url",
+            ResourceKind::Ifix => "ifix_url",
+        }
+    }
+}
+
+impl FromStr for ResourceKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().trim_end_matches("_url") {
+            "asset_bundle" | "asb" => Ok(ResourceKind::AssetBundle),
+            "ex_resource" | "design_data" => Ok(ResourceKind::ExResource),
+            "lua" => Ok(ResourceKind::Lua),
+            "ifix" => Ok(ResourceKind::Ifix),
+            _ => Err(format!("unknown resource: {s}")),
+        }
+    }
+}
+
+/// Substrings identifying the URL of each resource kind, and the build it points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlPatterns {
+    pub asset_bundle: String,
+    pub ex_resource: String,
+    pub lua: String,
+    pub ifix: String,
+    /// Prefix of the path segment holding the build number, as in `output_<version>_<hash>`.
+    pub version_prefix: String,
+}
+
+impl Default for UrlPatterns {
+    fn default() -> Self {
+        Self {
+            asset_bundle: String::from("/asb/"),
+            ex_resource: String::from("/design_data/"),
+            lua: String::from("/lua/"),
+            ifix: String::from("/ifix/"),
+            version_prefix: String::from(DEFAULT_VERSION_PREFIX),
+        }
+    }
+}
+
+impl UrlPatterns {
+    /// Returns the pattern identifying URLs of the given resource kind.
+    pub fn pattern(&self, kind: ResourceKind) -> &str {
+        match kind {
+            ResourceKind::AssetBundle => &self.asset_bundle,
+            ResourceKind::ExResource => &self.ex_resource,
+            ResourceKind::Lua => &self.lua,
+            ResourceKind::Ifix => &self.ifix,
+        }
+    }
+}
+
+/// Returns the kind of resource `url` points to, or `None` if it doesn't match any pattern.
+/// Only the path of the URL is matched, its query string and fragment are ignored.
+/// Kinds are tried in the order of `ResourceKind::ALL`, the first match wins.
+pub fn classify_url(url: &str, patterns: &UrlPatterns) -> Option<ResourceKind> {
+    let path = match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed.path().to_string(),
+        Err(_) => url.split(['?', '#']).next().unwrap_or_default().to_string(),
+    };
+
+    ResourceKind::ALL
+        .into_iter()
+        .find(|&kind| path.contains(patterns.pattern(kind)))
+}
+
+/// Default prefix of the path segment holding the build number of a resource URL.
+pub const DEFAULT_VERSION_PREFIX: &str = "output_";
+
+/// The parts of a resource URL needed to identify the build it points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedResourceUrl {
+    /// Host of the CDN serving the resource.
+    pub host: String,
+    /// Path of the URL, without the query string.
+    pub path: String,
+    /// The `output_<version>_<hash>` path segment, if the URL has one. See `parse_with` for other prefixes.
+    pub version_segment: Option<String>,
+    /// The path segment before `version_segment` naming the release, e.g. `V2.2Live`.
+    pub release_segment: Option<String>,
+}
+
+impl ParsedResourceUrl {
+    /// Parses `url`, returning `None` if it is not an absolute URL with a host.
+    pub fn parse(url: &str) -> Option<Self> {
+        Self::parse_with(url, DEFAULT_VERSION_PREFIX)
+    }
+
+    /// Parses `url` like `parse`, taking the version segment to be the first one starting with `version_prefix`.
+    pub fn parse_with(url: &str, version_prefix: &str) -> Option<Self> {
+        let url = reqwest::Url::parse(url).ok()?;
+        let segments: Vec<&str> = url.path_segments()?.collect();
+        let output_index = segments
+            .iter()
+            .position(|segment| segment.starts_with(version_prefix));
+
+        Some(Self {
+            host: url.host_str()?.to_string(),
+            path: url.path().to_string(),
+            version_segment: output_index.map(|i| segments[i].to_string()),
+            release_segment: output_index
+                .and_then(|i| i.checked_sub(1))
+                .map(|i| segments[i].to_string()),
+        })
+    }
+}
+
+/// Returns the build number of a resource URL, taken from its path segment starting with `patterns.version_prefix`,
+/// e.g. `output_<version>_<hash>`.
+pub fn parse_resource_version(url: &str, patterns: &UrlPatterns) -> Option<u32> {
+    ParsedResourceUrl::parse_with(url, &patterns.version_prefix)?
+        .version_segment?
+        .strip_prefix(patterns.version_prefix.as_str())?
+        .split('_')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Returns the game version a resource URL was released for, taken from the path segment before
+/// `output_<version>_<hash>`, e.g. `V2.2Live`. Returns `None` for releases without a version like `BetaLive`.
+pub fn parse_release_version(url: &str) -> Option<String> {
+    ParsedResourceUrl::parse(url)?
+        .release_segment
+        .filter(|segment| segment.contains(|c: char| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "https://autopatchcn.bhsr.com";
+
+    #[test]
+    fn classifies_each_resource_kind() {
+        let patterns = UrlPatterns::default();
+
+        for (path, kind) in [
+            (
+                "/asb/V2.2Live/output_7563532_5c6f0d11b0e5/client",
+                ResourceKind::AssetBundle,
+            ),
+            (
+                "/design_data/V2.2Live/output_7581234_9a1b2c3d4e5f/client",
+                ResourceKind::ExResource,
+            ),
+            (
+                "/lua/V2.2Live/output_7576543_0f1e2d3c4b5a/client",
+                ResourceKind::Lua,
+            ),
+            (
+                "/ifix/V2.2Live/output_7570000_aabbccddeeff/client",
+                ResourceKind::Ifix,
+            ),
+        ] {
+            assert_eq!(
+                classify_url(&format!("{BASE}{path}"), &patterns),
+                Some(kind)
+            );
+        }
+    }
+
+    #[test]
+    fn leaves_other_urls_unclassified() {
+        let patterns = UrlPatterns::default();
+
+        assert_eq!(
+            classify_url(&format!("{BASE}/client/Windows"), &patterns),
+            None
+        );
+        assert_eq!(classify_url("127.0.0.1", &patterns), None);
+    }
+
+    #[test]
+    fn uses_custom_patterns() {
+        let patterns = UrlPatterns {
+            lua: String::from("/scripts/"),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            classify_url(&format!("{BASE}/scripts/output_1_a/client"), &patterns),
+            Some(ResourceKind::Lua)
+        );
+        assert_eq!(
+            classify_url(&format!("{BASE}/lua/output_1_a/client"), &patterns),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_each_resource_url_kind() {
+        for (kind, version_segment) in [
+            ("asb", "output_7563532_5c6f0d11b0e5"),
+            ("design_data", "output_7581234_9a1b2c3d4e5f"),
+            ("lua", "output_7576543_0f1e2d3c4b5a"),
+            ("ifix", "output_7570000_aabbccddeeff"),
+        ] {
+            let path = format!("/{kind}/V2.2Live/{version_segment}/client");
+
+            assert_eq!(
+                ParsedResourceUrl::parse(&format!("{BASE}{path}")),
+                Some(ParsedResourceUrl {
+                    host: String::from("autopatchcn.bhsr.com"),
+                    path,
+                    version_segment: Some(version_segment.to_string()),
+                    release_segment: Some(String::from("V2.2Live")),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn parses_urls_without_a_version_segment() {
+        let parsed = ParsedResourceUrl::parse(&format!("{BASE}/client/Windows")).unwrap();
+
+        assert_eq!(parsed.path, "/client/Windows");
+        assert_eq!(parsed.version_segment, None);
+        assert_eq!(parsed.release_segment, None);
+        assert_eq!(ParsedResourceUrl::parse("/lua/output_1_a/client"), None);
+    }
+
+    #[test]
+    fn parses_the_release_version_of_resource_urls() {
+        assert_eq!(
+            parse_release_version(&format!(
+                "{BASE}/lua/V2.2Live/output_7576543_0f1e2d3c4b5a/client"
+            ))
+            .as_deref(),
+            Some("V2.2Live")
+        );
+        assert_eq!(
+            parse_release_version(&format!(
+                "{BASE}/lua/BetaLive/output_7576543_0f1e2d3c4b5a/client"
+            )),
+            None
+        );
+        assert_eq!(
+            parse_release_version(&format!("{BASE}/output_7576543_0f1e2d3c4b5a/client")),
+            None
+        );
+    }
+
+    #[test]
+    fn ignores_query_strings_and_fragments() {
+        let patterns = UrlPatterns::default();
+        let url =
+            format!("{BASE}/lua/V2.2Live/output_7576543_0f1e2d3c4b5a/client?token=abc/asb/#/ifix/");
+
+        assert_eq!(classify_url(&url, &patterns), Some(ResourceKind::Lua));
+        assert_eq!(parse_resource_version(&url, &patterns), Some(7576543));
+        assert_eq!(
+            classify_url(&format!("{BASE}/client?next=/asb/"), &patterns),
+            None
+        );
+        // Relative URLs are matched on the part before the query string
+        assert_eq!(
+            classify_url("/client?next=/lua/output_1_a", &patterns),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_the_build_of_each_resource_kind() {
+        for (path, version) in [
+            ("/asb/V2.2Live/output_7563532_5c6f0d11b0e5/client", 7563532),
+            (
+                "/design_data/V2.2Live/output_7581234_9a1b2c3d4e5f/client",
+                7581234,
+            ),
+            ("/lua/V2.2Live/output_7576543_0f1e2d3c4b5a/client", 7576543),
+            ("/ifix/V2.2Live/output_7570000_aabbccddeeff/client", 7570000),
+        ] {
+            assert_eq!(
+                parse_resource_version(&format!("{BASE}{path}"), &UrlPatterns::default()),
+                Some(version),
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn urls_without_a_build_have_no_version() {
+        for url in [
+            format!("{BASE}/lua/V2.2Live/client"),
+            format!("{BASE}/lua/V2.2Live/output_latest_0f1e2d3c4b5a/client"),
+            format!("{BASE}/lua/V2.2Live/output_/client"),
+            String::new(),
+        ] {
+            assert_eq!(
+                parse_resource_version(&url, &UrlPatterns::default()),
+                None,
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_the_build_with_a_custom_version_prefix() {
+        let patterns = UrlPatterns {
+            version_prefix: String::from("build-"),
+            ..Default::default()
+        };
+        let url = format!("{BASE}/lua/V2.2Live/build-7576543_0f1e2d3c4b5a/client");
+
+        assert_eq!(parse_resource_version(&url, &patterns), Some(7576543));
+        assert_eq!(parse_resource_version(&url, &UrlPatterns::default()), None);
+        assert_eq!(
+            parse_resource_version(
+                &format!("{BASE}/lua/V2.2Live/output_7576543_0f1e2d3c4b5a/client"),
+                &patterns
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! Helpers shared by the unit tests: a mock HTTP server and fixtures of the dispatch and gateway responses.
+
+use crate::{
+    base64,
+    proto::{Dispatch, RegionInfo},
+};
+use prost::Message;
+use std::{
+    cell::RefCell,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        Arc, Mutex, Once,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+/// Region listed by `dispatch_response`.
+pub(crate) const REGION_NAME: &str = "prod_gf_cn";
+/// Dispatch seed sent with the mock queries.
+pub(crate) const DISPATCH_SEED: &str = "seed";
+
+pub(crate) const ASSET_BUNDLE_URL: &str =
+    "https://autopatchcn.bhsr.com/asb/V2.2Live/output_7576543_5c6f0d11b0e5/client";
+pub(crate) const EX_RESOURCE_URL: &str =
+    "https://autopatchcn.bhsr.com/design_data/V2.2Live/output_7581234_9a1b2c3d4e5f/client";
+pub(crate) const LUA_URL: &str =
+    "https://autopatchcn.bhsr.com/lua/V2.2Live/output_7576543_0f1e2d3c4b5a/client";
+pub(crate) const IFIX_URL: &str =
+    "https://autopatchcn.bhsr.com/ifix/V2.2Live/output_7570000_aabbccddeeff/client";
+
+/// A request received by a `MockServer`.
+#[derive(Debug, Clone)]
+pub(crate) struct MockRequest {
+    /// Path of the request, with its query string.
+    pub path: String,
+    /// Headers of the request, with lowercase names.
+    pub headers: Vec<(String, String)>,
+}
+
+impl MockRequest {
+    /// Returns the value of the header `name`, given in lowercase.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The response a `MockServer` answers a request with.
+#[derive(Debug, Clone)]
+pub(crate) struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    delay: Duration,
+}
+
+impl MockResponse {
+    /// A `200 OK` response with the given body.
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self::status(200, body)
+    }
+
+    /// A response with the given status and body.
+    pub fn status(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Waits `delay` before answering, e.g. to trigger a client timeout.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// An HTTP server on a local port answering each request with the response picked by its handler.
+/// The handler is given the request and the number of requests received before it.
+pub(crate) struct MockServer {
+    base_url: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockServer {
+    pub fn start(
+        handler: impl Fn(&MockRequest, usize) -> MockResponse + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let handler = Arc::new(handler);
+        let received = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let handler = Arc::clone(&handler);
+                let received = Arc::clone(&received);
+                thread::spawn(move || serve(stream, &*handler, &received));
+            }
+        });
+
+        Self { base_url, requests }
+    }
+
+    /// Returns the URL of `path` on this server.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url)
+    }
+
+    /// Returns every request received so far, in order.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Answers the single request sent on `stream`, then closes it.
+fn serve(
+    mut stream: TcpStream,
+    handler: &(impl Fn(&MockRequest, usize) -> MockResponse + ?Sized),
+    requests: &Mutex<Vec<MockRequest>>,
+) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .to_string();
+
+    // The requests are all GETs or HEADs, without a body after the headers
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    let request = MockRequest { path, headers };
+    // The lock is released before calling the handler, so that concurrent requests are handled concurrently
+    let index = {
+        let mut requests = requests.lock().unwrap();
+        requests.push(request.clone());
+        requests.len() - 1
+    };
+    let response = handler(&request, index);
+
+    thread::sleep(response.delay);
+
+    let mut head = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+
+    // The client may have given up waiting, e.g. after a timeout
+    let _ = stream.write_all(head.as_bytes());
+    let _ = stream.write_all(&response.body);
+}
+
+/// Appends a length-delimited field to `buf`.
+pub(crate) fn push_len_field(buf: &mut Vec<u8>, number: u32, bytes: &[u8]) {
+    prost::encoding::bytes::encode(number, &bytes.to_vec(), buf);
+}
+
+/// Appends a varint field to `buf`.
+pub(crate) fn push_varint_field(buf: &mut Vec<u8>, number: u32, value: u64) {
+    prost::encoding::uint64::encode(number, &value, buf);
+}
+
+/// A gateserver message holding every resource URL, the Lua build as `mdk_res_version`, an address and a port.
+/// The address must not decode as a message, as "127.0.0.1" does, for the field to be inferred as `ip`.
+pub(crate) fn gateserver_message() -> Vec<u8> {
+    let mut buf = Vec::new();
+    push_len_field(&mut buf, 1, REGION_NAME.as_bytes());
+    push_len_field(&mut buf, 2, b"10.0.0.1");
+    push_varint_field(&mut buf, 3, 23301);
+    push_len_field(&mut buf, 4, ASSET_BUNDLE_URL.as_bytes());
+    push_len_field(&mut buf, 5, EX_RESOURCE_URL.as_bytes());
+    push_len_field(&mut buf, 6, LUA_URL.as_bytes());
+    push_len_field(&mut buf, 7, IFIX_URL.as_bytes());
+    push_len_field(&mut buf, 8, b"7576543");
+    buf
+}
+
+/// The base64 gateway response carrying `gateserver_message`.
+pub(crate) fn gateway_response() -> String {
+    base64::encode(&gateserver_message())
+}
+
+/// The base64 dispatch response listing a single region, whose gateway is `gateway_url`.
+pub(crate) fn dispatch_response(gateway_url: &str) -> String {
+    let dispatch = Dispatch {
+        region_list: vec![RegionInfo {
+            name: REGION_NAME.to_string(),
+            title: String::from("China"),
+            dispatch_url: gateway_url.to_string(),
+            env_type: String::from("2"),
+            display_name: String::from("China"),
+            msg: String::from("OK"),
+        }],
+        ..Default::default()
+    };
+    base64::encode(&dispatch.encode_to_vec())
+}
+
+/// A server answering `/dispatch` with `dispatch_response` pointing to its `/gateway`, which answers with `gateway`.
+/// `gateway` is given the number of gateway requests received before.
+pub(crate) fn dispatch_and_gateway(
+    gateway: impl Fn(usize) -> MockResponse + Send + Sync + 'static,
+) -> MockServer {
+    // The dispatch response has to name the server's own address, which is only known once it is bound
+    let gateway_url = Arc::new(Mutex::new(String::new()));
+    let url = Arc::clone(&gateway_url);
+    let gateway_requests = AtomicUsize::new(0);
+    let server = MockServer::start(move |request, _| {
+        if request.path.starts_with("/dispatch") {
+            MockResponse::ok(dispatch_response(&url.lock().unwrap()))
+        } else {
+            gateway(gateway_requests.fetch_add(1, Ordering::SeqCst))
+        }
+    });
+    *gateway_url.lock().unwrap() = server.url("/gateway");
+    server
+}
+
+thread_local! {
+    /// Records logged on this thread, while `capture_logs` is collecting them.
+    static CAPTURED_LOGS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Logger keeping the records of the threads running `capture_logs`, as `<level> <message>`.
+struct CapturingLogger;
+
+impl log::Log for CapturingLogger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        CAPTURED_LOGS.with_borrow_mut(|captured| {
+            if let Some(captured) = captured {
+                captured.push(format!("{} {}", record.level(), record.args()));
+            }
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+/// Runs `f` and returns the records it logged, at every level.
+pub(crate) fn capture_logs(f: impl FnOnce()) -> Vec<String> {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        log::set_logger(&CapturingLogger).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+    });
+
+    CAPTURED_LOGS.set(Some(Vec::new()));
+    f();
+    CAPTURED_LOGS.take().unwrap_or_default()
+}
@@ -1,34 +1,237 @@
+use crate::base64;
 use std::{
     io::{Cursor, Read},
     net::Ipv4Addr,
 };
-use varint_rs::VarintReader;
 
 /// Opens a file dialog to allow the user to select a folder.
+/// The dialog starts in `start_dir` if given, else in the previously selected folder, if any,
+/// and remembers the new selection.
 /// Returns the selected folder's path, or `None` if the selection is canceled.
-pub fn select_folder() -> Option<std::path::PathBuf> {
-    rfd::FileDialog::new()
-        .set_directory(".")
+pub fn select_folder(start_dir: Option<&std::path::Path>) -> Option<std::path::PathBuf> {
+    let state_path = last_folder_state_path();
+
+    let folder = rfd::FileDialog::new()
+        .set_directory(dialog_start_dir(start_dir, state_path.as_deref()))
         .set_title("Select HSR Folder")
-        .pick_folder()
+        .pick_folder()?;
+
+    if let Some(state_path) = &state_path
+        && let Err(e) = write_last_folder(state_path, &folder)
+    {
+        log::debug!("Failed to remember the selected folder: {e}");
+    }
+
+    Some(folder)
+}
+
+/// Returns the folder the dialog of `select_folder` opens in: `start_dir` if given, else the folder remembered
+/// in the state file at `state_path`, else the current directory.
+pub fn dialog_start_dir(
+    start_dir: Option<&std::path::Path>,
+    state_path: Option<&std::path::Path>,
+) -> std::path::PathBuf {
+    start_dir
+        .map(std::path::Path::to_path_buf)
+        .or_else(|| state_path.and_then(read_last_folder))
+        .unwrap_or_else(|| std::path::PathBuf::from("."))
+}
+
+/// Path of the file remembering the last selected folder, in the user's local data (Windows) or state directory.
+pub fn last_folder_state_path() -> Option<std::path::PathBuf> {
+    let state_dir = if cfg!(windows) {
+        std::path::PathBuf::from(std::env::var_os("LOCALAPPDATA")?)
+    } else if let Some(dir) = std::env::var_os("XDG_STATE_HOME") {
+        std::path::PathBuf::from(dir)
+    } else {
+        std::path::PathBuf::from(std::env::var_os("HOME")?).join(".local/state")
+    };
+    Some(state_dir.join("fetch-hotfix").join("last_folder"))
+}
+
+/// Reads the folder remembered in the state file at `state_path`, if it still exists.
+pub fn read_last_folder(state_path: &std::path::Path) -> Option<std::path::PathBuf> {
+    let folder = std::path::PathBuf::from(std::fs::read_to_string(state_path).ok()?.trim_end());
+    folder.is_dir().then_some(folder)
+}
+
+/// Remembers `folder` in the state file at `state_path`, creating its parent directories if needed.
+pub fn write_last_folder(
+    state_path: &std::path::Path,
+    folder: &std::path::Path,
+) -> std::io::Result<()> {
+    if let Some(parent) = state_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(state_path, folder.as_os_str().as_encoded_bytes())
+}
+
+/// Returns the path to the "BinaryVersion.bytes" file located under `assets_subpath` from the given base path.
+/// `assets_subpath` is usually the platform's `streaming_assets_path`.
+pub fn get_binary_version_path(
+    base: &std::path::Path,
+    assets_subpath: &std::path::Path,
+) -> std::path::PathBuf {
+    base.join(assets_subpath).join("BinaryVersion.bytes")
+}
+
+/// Returns the path to the "ClientConfig.bytes" file located under `assets_subpath` from the given base path.
+/// `assets_subpath` is usually the platform's `streaming_assets_path`.
+pub fn get_client_config_path(
+    base: &std::path::Path,
+    assets_subpath: &std::path::Path,
+) -> std::path::PathBuf {
+    base.join(assets_subpath).join("ClientConfig.bytes")
+}
+
+/// Returns the game files read from `base` that don't exist under `assets_subpath`,
+/// meaning `base` is not a game folder, or not one for the expected platform.
+pub fn missing_game_files(
+    base: &std::path::Path,
+    assets_subpath: &std::path::Path,
+) -> Vec<std::path::PathBuf> {
+    [
+        get_binary_version_path(base, assets_subpath),
+        get_client_config_path(base, assets_subpath),
+    ]
+    .into_iter()
+    .filter(|path| !path.is_file())
+    .collect()
+}
+
+/// Default cap on the size of the game files read by `read_capped`. BinaryVersion and ClientConfig are a few hundred bytes.
+pub const DEFAULT_MAX_GAME_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Reads the whole file at `path`, failing with `InvalidData` instead if it is larger than `max_len` bytes.
+pub fn read_capped(path: &std::path::Path, max_len: u64) -> std::io::Result<Vec<u8>> {
+    let file = std::fs::File::open(path)?;
+
+    // Read one byte past the cap so an oversized file is detected without loading all of it
+    let mut buffer = Vec::new();
+    file.take(max_len.saturating_add(1))
+        .read_to_end(&mut buffer)?;
+    if buffer.len() as u64 > max_len {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "{} is larger than {max_len} bytes, is it the right file?",
+                path.display()
+            ),
+        ));
+    }
+
+    Ok(buffer)
+}
+
+/// Reads the whole file at `path` as text, or standard input if `path` is "-".
+pub fn read_input(path: &std::path::Path) -> std::io::Result<String> {
+    read_input_with(path, std::io::stdin())
+}
+
+/// Reads the whole file at `path` as text, or `stdin` if `path` is "-".
+pub fn read_input_with(path: &std::path::Path, stdin: impl Read) -> std::io::Result<String> {
+    if path.as_os_str() == "-" {
+        std::io::read_to_string(stdin)
+    } else {
+        std::fs::read_to_string(path)
+    }
+}
+
+/// Decodes hex text, ignoring whitespace and a `0x` prefix on each whitespace separated group of digits.
+pub fn decode_hex(s: &str) -> Result<Vec<u8>, String> {
+    let digits = s
+        .split_whitespace()
+        .map(|group| {
+            group
+                .strip_prefix("0x")
+                .or_else(|| group.strip_prefix("0X"))
+                .unwrap_or(group)
+        })
+        .collect::<String>();
+
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!("odd number of hex digits ({})", digits.len()));
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            let pair = digits
+                .get(i..i + 2)
+                .ok_or_else(|| format!("invalid hex digit near offset {i}"))?;
+            u8::from_str_radix(pair, 16).map_err(|_| format!("invalid hex digits {pair:?}"))
+        })
+        .collect()
 }
 
-/// Returns the path to the "BinaryVersion.bytes" file located under "StarRail_Data/StreamingAssets" from the given base path.
-pub fn get_binary_version_path(base: &std::path::Path) -> std::path::PathBuf {
-    base.join("StarRail_Data/StreamingAssets/BinaryVersion.bytes")
+/// Returns the text currently held by the system clipboard.
+#[cfg(feature = "clipboard")]
+pub fn read_clipboard() -> Result<String, arboard::Error> {
+    arboard::Clipboard::new()?.get_text()
 }
 
-/// Returns the path to the "ClientConfig.bytes" file located under "StarRail_Data/StreamingAssets" from the given base path.
-pub fn get_client_config_path(base: &std::path::Path) -> std::path::PathBuf {
-    base.join("StarRail_Data/StreamingAssets/ClientConfig.bytes")
+/// Compares two version strings by their numeric components, ignoring any prefix or build suffix
+/// and missing trailing zeros, e.g. `OSPRODWin3.0.0` matches `3.0` but not `3.0.1-beta`.
+pub fn versions_match(a: &str, b: &str) -> bool {
+    fn components(version: &str) -> Vec<u32> {
+        let start = version
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(version.len());
+        let mut components: Vec<u32> = version[start..]
+            .split(|c: char| !c.is_ascii_digit() && c != '.')
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map_while(|part| part.parse().ok())
+            .collect();
+        while components.last() == Some(&0) {
+            components.pop();
+        }
+        components
+    }
+
+    components(a) == components(b)
+}
+
+/// Formats up to the first `max_len` bytes as hex followed by their printable ASCII, e.g. `3c 21 44 |<!D|`.
+pub fn byte_preview(bytes: &[u8], max_len: usize) -> String {
+    let shown = &bytes[..bytes.len().min(max_len)];
+
+    let hex = shown
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let ascii = shown
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect::<String>();
+    let ellipsis = if bytes.len() > max_len { " ..." } else { "" };
+
+    format!("{hex} |{ascii}|{ellipsis}")
 }
 
 pub fn get_ip_address(s: &str) -> Option<String> {
     s.parse::<Ipv4Addr>().map(|v| v.to_string()).ok()
 }
 
+/// Returns whether `bytes` are padded base64 text, i.e. a non-empty multiple of 4 characters from the base64 alphabet.
+pub fn is_base64_text(bytes: &[u8]) -> bool {
+    !bytes.is_empty()
+        && bytes.len().is_multiple_of(4)
+        && bytes
+            .iter()
+            .all(|&b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'='))
+}
+
 pub fn is_ec2b_base64(s: &str) -> bool {
-    rbase64::decode(s)
+    base64::decode(s)
         .map(|v| v.starts_with(&[69, 99, 50, 98])) // "Ec2b"
         .unwrap_or_default()
 }
@@ -38,9 +241,19 @@ pub trait CursorExt {
     fn read_string(&mut self) -> Result<String, Self::Error>;
     fn read_bool(&mut self) -> Result<bool, Self::Error>;
     fn read_u32_be(&mut self) -> Result<u32, Self::Error>;
+    fn read_u32_varint(&mut self) -> Result<u32, Self::Error>;
     fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>, Self::Error>;
 }
 
+/// Fails with `UnexpectedEof` if fewer than `len` bytes are left, so that bogus lengths don't trigger huge allocations.
+fn ensure_remaining(cursor: &Cursor<Vec<u8>>, len: usize) -> std::io::Result<()> {
+    let remaining = (cursor.get_ref().len() as u64).saturating_sub(cursor.position());
+    if (len as u64) > remaining {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
 impl CursorExt for Cursor<Vec<u8>> {
     type Error = std::io::Error;
 
@@ -53,9 +266,12 @@ impl CursorExt for Cursor<Vec<u8>> {
     fn read_string(&mut self) -> Result<String, Self::Error> {
         self.read_bool()?;
         let length = self.read_u32_varint()? as usize;
+        ensure_remaining(self, length)?;
         let mut buffer = vec![0u8; length];
         self.read_exact(&mut buffer)?;
-        Ok(String::from_utf8_lossy(&buffer).to_string())
+        // Some files prefix their strings with a UTF-8 BOM, which would break comparisons
+        let text = buffer.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&buffer);
+        Ok(String::from_utf8_lossy(text).to_string())
     }
 
     fn read_u32_be(&mut self) -> Result<u32, Self::Error> {
@@ -64,9 +280,211 @@ impl CursorExt for Cursor<Vec<u8>> {
         Ok(u32::from_be_bytes(buffer))
     }
 
+    fn read_u32_varint(&mut self) -> Result<u32, Self::Error> {
+        let mut value = 0_u32;
+
+        // A u32 takes at most 5 bytes, anything longer is malformed
+        for shift in (0..35).step_by(7) {
+            let mut byte = [0; 1];
+            self.read_exact(&mut byte)?;
+            value |= ((byte[0] & 0x7F) as u32).wrapping_shl(shift);
+            if byte[0] & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "varint is too long for a u32",
+        ))
+    }
+
     fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>, Self::Error> {
+        ensure_remaining(self, len)?;
         let mut buffer = vec![0u8; len];
         self.read_exact(&mut buffer)?;
         Ok(buffer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fetch::decode_gateway_response,
+        metadata::Metadata,
+        params::DEFAULT_ENVELOPE_FIELD,
+        test_util::{LUA_URL, gateway_response},
+    };
+    use std::path::Path;
+
+    #[test]
+    fn reads_the_gateway_response_from_stdin() {
+        let stdin = Cursor::new(format!("{}\n", gateway_response()));
+
+        let response = read_input_with(Path::new("-"), stdin).unwrap();
+        let output =
+            decode_gateway_response(&response, Metadata::default(), DEFAULT_ENVELOPE_FIELD)
+                .unwrap();
+
+        assert_eq!(output.hotfix.lua_url, LUA_URL);
+    }
+
+    #[test]
+    fn read_string_strips_a_leading_bom() {
+        let mut data = vec![1, 9];
+        data.extend_from_slice(b"\xEF\xBB\xBFCNPROD");
+        data.extend_from_slice(&[1, 3]);
+        data.extend_from_slice(b"Win");
+        let mut cursor = Cursor::new(data);
+
+        assert_eq!(cursor.read_string().unwrap(), "CNPROD");
+        assert_eq!(cursor.read_string().unwrap(), "Win");
+    }
+
+    #[test]
+    fn resolves_game_files_under_a_custom_subpath() {
+        let game = tempfile::tempdir().unwrap();
+        let subpath = Path::new("Repack/Data");
+        let assets = game.path().join(subpath);
+        std::fs::create_dir_all(&assets).unwrap();
+        std::fs::write(assets.join("BinaryVersion.bytes"), b"").unwrap();
+        std::fs::write(assets.join("ClientConfig.bytes"), b"").unwrap();
+
+        assert_eq!(
+            get_binary_version_path(game.path(), subpath),
+            assets.join("BinaryVersion.bytes")
+        );
+        assert_eq!(
+            get_client_config_path(game.path(), subpath),
+            assets.join("ClientConfig.bytes")
+        );
+        assert!(missing_game_files(game.path(), subpath).is_empty());
+        assert_eq!(
+            missing_game_files(game.path(), Path::new("StarRail_Data/StreamingAssets")).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn read_capped_rejects_oversized_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("BinaryVersion.bytes");
+        std::fs::write(&path, vec![0; 1025]).unwrap();
+
+        let error = read_capped(&path, 1024).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("larger than 1024 bytes"));
+
+        assert_eq!(read_capped(&path, 1025).unwrap().len(), 1025);
+    }
+
+    #[test]
+    fn matches_versions_in_different_formats() {
+        for (a, b) in [
+            ("OSPRODWin3.0.0", "3.0"),
+            ("V2.2Live", "2.2.0"),
+            ("CNPRODWin2.2.0", "2.2.0"),
+            ("2.2.0-7563532", "2.2"),
+        ] {
+            assert!(versions_match(a, b), "{a} {b}");
+            assert!(versions_match(b, a), "{b} {a}");
+        }
+    }
+
+    #[test]
+    fn tells_different_versions_apart() {
+        for (a, b) in [
+            ("3.0.1-beta", "3.0"),
+            ("2.2.0", "2.3.0"),
+            ("V2.2Live", "2.2.51"),
+            ("CNPRODWin2.2.0", "12.2.0"),
+        ] {
+            assert!(!versions_match(a, b), "{a} {b}");
+        }
+    }
+
+    #[test]
+    fn remembers_the_last_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_path = dir.path().join("state").join("last_folder");
+        let folder = dir.path().join("Star Rail");
+        std::fs::create_dir(&folder).unwrap();
+
+        assert_eq!(read_last_folder(&state_path), None);
+
+        write_last_folder(&state_path, &folder).unwrap();
+        assert_eq!(read_last_folder(&state_path), Some(folder.clone()));
+
+        // A folder that was removed since is not offered again
+        std::fs::remove_dir(&folder).unwrap();
+        assert_eq!(read_last_folder(&state_path), None);
+    }
+
+    #[test]
+    fn dialog_starts_in_the_chosen_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_path = dir.path().join("last_folder");
+        let remembered = dir.path().join("remembered");
+        std::fs::create_dir(&remembered).unwrap();
+        let start_dir = Path::new("/games/SteamLibrary");
+
+        assert_eq!(dialog_start_dir(None, None), Path::new("."));
+        assert_eq!(dialog_start_dir(None, Some(&state_path)), Path::new("."));
+
+        write_last_folder(&state_path, &remembered).unwrap();
+        assert_eq!(dialog_start_dir(None, Some(&state_path)), remembered);
+        assert_eq!(
+            dialog_start_dir(Some(start_dir), Some(&state_path)),
+            start_dir
+        );
+    }
+
+    #[test]
+    fn names_the_missing_game_files() {
+        let game = tempfile::tempdir().unwrap();
+        let subpath = Path::new("StarRail_Data/StreamingAssets");
+
+        assert_eq!(
+            missing_game_files(game.path(), subpath),
+            [
+                get_binary_version_path(game.path(), subpath),
+                get_client_config_path(game.path(), subpath),
+            ]
+        );
+
+        let assets = game.path().join(subpath);
+        std::fs::create_dir_all(&assets).unwrap();
+        std::fs::write(assets.join("BinaryVersion.bytes"), b"").unwrap();
+        assert_eq!(
+            missing_game_files(game.path(), subpath),
+            [assets.join("ClientConfig.bytes")]
+        );
+
+        // A directory with the name of a game file doesn't count
+        std::fs::create_dir(assets.join("ClientConfig.bytes")).unwrap();
+        assert_eq!(missing_game_files(game.path(), subpath).len(), 1);
+    }
+
+    #[test]
+    fn decode_hex_ignores_whitespace_and_prefixes() {
+        assert_eq!(
+            decode_hex("0x0885 b601\n0X1203\tabCD\r\n"),
+            Ok(vec![0x08, 0x85, 0xB6, 0x01, 0x12, 0x03, 0xAB, 0xCD])
+        );
+        assert_eq!(decode_hex(""), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn decode_hex_rejects_malformed_input() {
+        assert_eq!(
+            decode_hex("0x123"),
+            Err(String::from("odd number of hex digits (3)"))
+        );
+        assert_eq!(
+            decode_hex("12zz"),
+            Err(String::from("invalid hex digits \"zz\""))
+        );
+        assert!(decode_hex("12é4").is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! Property tests checking that decoding the output of `DecodingResult::encode` yields the encoded fields,
+//! for the wire types the decoder fully models.
+
+use fetch_hotfix::decode::{
+    Decoded, DecodedValue, Decoder, DecoderOptions, DecodingResult, WireType,
+};
+use proptest::prelude::*;
+
+/// Largest field number allowed by protobuf.
+const MAX_FIELD_NUMBER: u32 = (1 << 29) - 1;
+
+fn field_number() -> impl Strategy<Value = u32> {
+    prop_oneof![1..=16_u32, 1..=MAX_FIELD_NUMBER]
+}
+
+/// A VarInt, I32 or I64 field.
+fn scalar_field() -> impl Strategy<Value = Decoded> {
+    let value = prop_oneof![
+        any::<u64>().prop_map(|num| (WireType::VarInt, DecodedValue::UInt(num.into()))),
+        any::<[u8; 4]>().prop_map(|buf| (WireType::I32, DecodedValue::Buffer(buf.to_vec()))),
+        any::<[u8; 8]>().prop_map(|buf| (WireType::I64, DecodedValue::Buffer(buf.to_vec()))),
+    ];
+
+    (field_number(), value).prop_map(|(field, (wire_type, value))| Decoded {
+        field,
+        wire_type,
+        is_object: false,
+        value,
+    })
+}
+
+/// A field of any of the modeled wire types, with Len fields holding raw bytes.
+fn flat_field() -> impl Strategy<Value = Decoded> {
+    let len_field =
+        (field_number(), prop::collection::vec(any::<u8>(), 0..64)).prop_map(|(field, bytes)| {
+            Decoded {
+                field,
+                wire_type: WireType::Len,
+                is_object: false,
+                value: DecodedValue::Buffer(bytes),
+            }
+        });
+
+    prop_oneof![scalar_field(), len_field]
+}
+
+/// A field of any of the modeled wire types, with Len fields holding nested messages.
+fn nested_field() -> impl Strategy<Value = Decoded> {
+    scalar_field().prop_recursive(4, 64, 8, |inner| {
+        (field_number(), prop::collection::vec(inner, 1..8)).prop_map(|(field, fields)| Decoded {
+            field,
+            wire_type: WireType::Len,
+            is_object: true,
+            value: DecodedValue::Nested(DecodingResult {
+                fields,
+                unprocessed: Vec::new(),
+            }),
+        })
+    })
+}
+
+/// Converts the fields into comparable values: field number, wire type, nesting and value.
+fn fields_json(fields: &[Decoded]) -> Vec<serde_json::Value> {
+    fields
+        .iter()
+        .map(|field| {
+            serde_json::json!({
+                "field": field.to_json(),
+                "is_object": field.is_object,
+            })
+        })
+        .collect()
+}
+
+fn message(fields: Vec<Decoded>) -> DecodingResult {
+    DecodingResult {
+        fields,
+        unprocessed: Vec::new(),
+    }
+}
+
+proptest! {
+    #[test]
+    fn flat_fields_round_trip(fields in prop::collection::vec(flat_field(), 0..32)) {
+        let expected = message(fields);
+        let options = DecoderOptions {
+            auto_nest: false,
+            ..Default::default()
+        };
+
+        let decoded = Decoder::with_options(expected.encode(), options).decode().unwrap();
+
+        prop_assert_eq!(fields_json(&decoded.fields), fields_json(&expected.fields));
+        prop_assert!(decoded.unprocessed.is_empty());
+    }
+
+    #[test]
+    fn nested_fields_round_trip(fields in prop::collection::vec(nested_field(), 0..16)) {
+        let expected = message(fields);
+
+        let decoded = Decoder::new(expected.encode()).decode().unwrap();
+
+        prop_assert_eq!(fields_json(&decoded.fields), fields_json(&expected.fields));
+        prop_assert!(decoded.validate());
+    }
+}